@@ -0,0 +1,98 @@
+use anyhow::Result;
+use clap::Parser;
+use dialoguer::Confirm;
+use splitwise::model::other::ParseSentenceRequest;
+use splitwise::model::other::SentencePreview;
+
+use crate::client::client;
+use crate::client::profile;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    /// Natural language description of the expense, eg "groceries $20"
+    sentence: String,
+
+    /// Splitwise group ID to add the expense to. Defaults to the default
+    /// group of the profile, unless adding with a friend.
+    #[clap(long)]
+    group_id: Option<i64>,
+
+    /// Splitwise friend ID to add the expense with
+    #[clap(long)]
+    friend_id: Option<i64>,
+
+    /// Minimum confidence required to accept the parsed expense
+    #[clap(long, default_value = "0.5")]
+    min_confidence: f64,
+
+    /// Assume yes to all prompts (ie, non-interactive)
+    #[clap(long)]
+    assume_yes: bool,
+}
+
+pub(crate) async fn add(args: Args) -> Result<()> {
+    let client = client()?;
+
+    let preview = client
+        .other()
+        .preview_sentence(
+            ParseSentenceRequest {
+                input: args.sentence,
+                group_id: match args.friend_id {
+                    Some(_) => args.group_id,
+                    None => args.group_id.or(profile().default_group),
+                },
+                friend_id: args.friend_id,
+                autosave: false,
+            },
+            args.min_confidence,
+        )
+        .await?;
+    print_preview(&preview);
+
+    let confirmed = args.assume_yes
+        || Confirm::new()
+            .with_prompt("Create this expense?")
+            .interact()
+            .unwrap_or(false);
+    if !confirmed {
+        return Ok(());
+    }
+
+    let created = client.other().commit_sentence(preview).await?;
+    for expense in created {
+        println!("Created expense {}", expense.id.unwrap_or_default());
+    }
+
+    Ok(())
+}
+
+fn print_preview(preview: &SentencePreview) {
+    let request = &preview.request;
+    println!("{} {}", request.cost, request.currency_code);
+    println!("  description: {}", request.description);
+    println!("  date:        {}", request.date.date_naive());
+    println!("  group:       {}", request.group_id);
+    println!("  confidence:  {:.2}", preview.confidence);
+
+    let shares = match request.users {
+        Some(ref shares) => shares,
+        None => {
+            println!("  split:       equally");
+            return;
+        }
+    };
+    for (share, parsed) in shares.iter().zip(preview.expense.users.iter().flatten()) {
+        let name = parsed
+            .user
+            .as_ref()
+            .and_then(|u| u.first_name.clone())
+            .unwrap_or_else(|| share.user_id.unwrap_or_default().to_string());
+        println!(
+            "  {}: paid {}, owes {}",
+            name,
+            share.paid_share.as_deref().unwrap_or("0.00"),
+            share.owed_share.as_deref().unwrap_or("0.00")
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use splitwise::archive::Archive;
+
+use crate::client::client;
+
+#[derive(Parser)]
+pub(crate) struct ExportArgs {
+    /// Path to write the archive to, instead of stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub(crate) struct ImportArgs {
+    /// Path to an archive written by `export`
+    file: PathBuf,
+
+    /// Splitwise API base URL to import into, eg a mock server
+    #[clap(long)]
+    base_url: Option<String>,
+
+    /// Path to write the ID remapping report to, instead of stdout
+    #[clap(short, long)]
+    report: Option<PathBuf>,
+}
+
+pub(crate) async fn export(args: ExportArgs) -> Result<()> {
+    let archive = splitwise::archive::export(&client()?).await?;
+    eprintln!(
+        "Exported {} groups, {} friends and {} expenses",
+        archive.groups.len(),
+        archive.friends.len(),
+        archive.expenses.len()
+    );
+
+    let writer: Box<dyn Write> = match args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_json::to_writer_pretty(writer, &archive)?;
+    Ok(())
+}
+
+pub(crate) async fn import(args: ImportArgs) -> Result<()> {
+    let archive: Archive = serde_json::from_reader(BufReader::new(File::open(args.file)?))?;
+
+    let mut client = client()?;
+    if let Some(base_url) = args.base_url {
+        client = client.with_base_url(&base_url)?;
+    }
+    let report = splitwise::archive::import(&client, &archive).await?;
+    eprintln!(
+        "Imported {} groups, {} expenses and {} comments, skipped {} friends, {} groups, {} expenses and {} comments",
+        report.groups.len(),
+        report.expenses.len(),
+        report.comments,
+        report.skipped_friends.len(),
+        report.skipped_groups.len(),
+        report.skipped_expenses.len(),
+        report.skipped_comments.len()
+    );
+
+    let writer: Box<dyn Write> = match args.report {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    serde_json::to_writer_pretty(writer, &report)?;
+    Ok(())
+}
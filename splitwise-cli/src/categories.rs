@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use splitwise::model::other::Category;
+
+use crate::transaction::Transaction;
+
+/// Splitwise subcategories for common bank and aggregator category names,
+/// checked in order against the words of lowercase bank categories, see
+/// `contains_word`.
+const DEFAULT_MAPPINGS: &[(&str, &str)] = &[
+    ("grocer", "Groceries"),
+    ("grocery", "Groceries"),
+    ("groceries", "Groceries"),
+    ("supermarket", "Groceries"),
+    ("restaurant", "Dining out"),
+    ("fast food", "Dining out"),
+    ("coffee", "Dining out"),
+    ("dining", "Dining out"),
+    ("food & drink", "Dining out"),
+    ("alcohol", "Liquor"),
+    ("bars", "Liquor"),
+    ("gas & fuel", "Gas/fuel"),
+    ("fuel", "Gas/fuel"),
+    ("parking", "Parking"),
+    ("taxi", "Taxi"),
+    ("ride share", "Taxi"),
+    ("rideshare", "Taxi"),
+    ("public transport", "Bus/train"),
+    ("public transportation", "Bus/train"),
+    ("train", "Bus/train"),
+    ("air travel", "Plane"),
+    ("airline", "Plane"),
+    ("hotel", "Hotel"),
+    ("auto insurance", "Insurance"),
+    ("insurance", "Insurance"),
+    ("auto", "Car"),
+    ("rent", "Rent"),
+    ("mortgage", "Mortgage"),
+    ("home improvement", "Maintenance"),
+    ("furnishing", "Furniture"),
+    ("furniture", "Furniture"),
+    ("electric", "Electricity"),
+    ("electricity", "Electricity"),
+    ("gas utility", "Heat/gas"),
+    ("gas utilities", "Heat/gas"),
+    ("water", "Water"),
+    ("trash", "Trash"),
+    ("internet", "TV/Phone/Internet"),
+    ("mobile phone", "TV/Phone/Internet"),
+    ("television", "TV/Phone/Internet"),
+    ("movie", "Movies"),
+    ("music", "Music"),
+    ("sport", "Sports"),
+    ("game", "Games"),
+    ("pharmacy", "Medical expenses"),
+    ("doctor", "Medical expenses"),
+    ("health", "Medical expenses"),
+    ("medical", "Medical expenses"),
+    ("clothing", "Clothing"),
+    ("electronics", "Electronics"),
+    ("gift", "Gifts"),
+    ("pet", "Pets"),
+    ("child", "Childcare"),
+    ("children", "Childcare"),
+    ("childcare", "Childcare"),
+    ("education", "Education"),
+    ("tuition", "Education"),
+    ("tax", "Taxes"),
+    ("cleaning", "Cleaning"),
+    ("household", "Household supplies"),
+];
+
+/// Splitwise category a bank category is mapped to by the user.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CategoryTarget {
+    Id(i64),
+    Name(String),
+}
+
+/// Resolves bank categories to Splitwise category IDs, using user overrides,
+/// then Splitwise category names, then the default mappings.
+#[derive(Debug, Default)]
+pub(crate) struct CategoryMap {
+    overrides: HashMap<String, i64>,
+    /// Lowercase Splitwise subcategory names to IDs
+    subcategories: HashMap<String, i64>,
+    /// Lowercase Splitwise top-level category names to their "Other" IDs
+    parents: HashMap<String, i64>,
+}
+
+impl CategoryMap {
+    /// Builds a map from the Splitwise category tree, with overrides read
+    /// from an optional TOML file of `"bank category" = "Splitwise category"`
+    /// or `"bank category" = <category ID>` entries.
+    pub fn new(categories: &[Category], overrides: Option<&Path>) -> Result<Self> {
+        let mut map = Self::default();
+        for parent in categories {
+            let subcategories = parent.subcategories.iter().flatten();
+            for category in subcategories.clone() {
+                if let (Some(id), Some(name)) = (category.id, category.name.as_ref()) {
+                    // Subcategory names like "Other" repeat, so keep the first
+                    map.subcategories.entry(name.to_lowercase()).or_insert(id);
+                }
+            }
+            let other = subcategories
+                .clone()
+                .find(|c| c.name.as_deref() == Some("Other"))
+                .and_then(|c| c.id)
+                .or(parent.id);
+            if let (Some(id), Some(name)) = (other, parent.name.as_ref()) {
+                map.parents.insert(name.to_lowercase(), id);
+            }
+        }
+
+        if let Some(path) = overrides {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("reading category map {}", path.display()))?;
+            let targets: BTreeMap<String, CategoryTarget> = toml::from_str(&contents)
+                .with_context(|| format!("parsing category map {}", path.display()))?;
+            for (bank, target) in targets {
+                let id = match target {
+                    CategoryTarget::Id(id) => id,
+                    CategoryTarget::Name(name) => map.by_name(&name).ok_or_else(|| {
+                        anyhow!("no Splitwise category named '{}' for '{}'", name, bank)
+                    })?,
+                };
+                map.overrides.insert(bank.to_lowercase(), id);
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Resolves the category of a transaction, trying its category and then
+    /// its parent category.
+    pub fn resolve(&self, txn: &Transaction) -> Option<i64> {
+        [txn.category.as_ref(), txn.category_parent.as_ref()]
+            .into_iter()
+            .flatten()
+            .find_map(|category| self.resolve_name(category))
+    }
+
+    fn resolve_name(&self, bank: &str) -> Option<i64> {
+        let bank = bank.to_lowercase();
+        self.overrides
+            .get(&bank)
+            .copied()
+            .or_else(|| self.by_name(&bank))
+            .or_else(|| {
+                DEFAULT_MAPPINGS
+                    .iter()
+                    .find(|(keyword, _)| contains_word(&bank, keyword))
+                    .and_then(|(_, name)| self.by_name(name))
+            })
+    }
+
+    fn by_name(&self, name: &str) -> Option<i64> {
+        let name = name.to_lowercase();
+        self.subcategories
+            .get(&name)
+            .or_else(|| self.parents.get(&name))
+            .copied()
+    }
+}
+
+/// Whether the text contains the words, as whole words or their plural with
+/// `s` or `es`, so that "pet" matches "pets" but not "carpet" or "petrol".
+fn contains_word(text: &str, words: &str) -> bool {
+    let is_word_char = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    text.match_indices(words).any(|(start, _)| {
+        let rest = &text[start + words.len()..];
+        !is_word_char(text[..start].chars().next_back())
+            && ["", "s", "es"].iter().any(|suffix| {
+                rest.strip_prefix(suffix)
+                    .is_some_and(|rest| !is_word_char(rest.chars().next()))
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use serde_json::json;
+
+    use super::*;
+
+    fn transaction(category: &str, parent: Option<&str>) -> Transaction {
+        Transaction {
+            id: "csv:1".to_string(),
+            date: NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            amount: Decimal::ONE,
+            currency: "USD".to_string(),
+            account: "Checking".to_string(),
+            description: "Shop".to_string(),
+            category: Some(category.to_string()),
+            category_parent: parent.map(|p| p.to_string()),
+        }
+    }
+
+    #[test]
+    fn resolve_uses_names_heuristics_and_parents() {
+        let categories: Vec<Category> = serde_json::from_value(json!([
+            {"id": 1, "name": "Food and drink", "subcategories": [
+                {"id": 12, "name": "Groceries"},
+                {"id": 13, "name": "Dining out"},
+                {"id": 26, "name": "Other"}
+            ]},
+            {"id": 2, "name": "Transportation", "subcategories": [
+                {"id": 33, "name": "Gas/fuel"}
+            ]}
+        ]))
+        .unwrap();
+        let map = CategoryMap::new(&categories, None).unwrap();
+
+        assert_eq!(map.resolve(&transaction("groceries", None)), Some(12));
+        assert_eq!(map.resolve(&transaction("Coffee Shops", None)), Some(13));
+        assert_eq!(map.resolve(&transaction("Gas & Fuel", None)), Some(33));
+        assert_eq!(
+            map.resolve(&transaction("Snacks", Some("Food and Drink"))),
+            Some(26)
+        );
+        assert_eq!(map.resolve(&transaction("Snacks", None)), None);
+    }
+
+    #[test]
+    fn default_mappings_match_whole_words() {
+        assert!(contains_word("pets", "pet"));
+        assert!(contains_word("pet food & supplies", "pet"));
+        assert!(contains_word("taxes", "tax"));
+        assert!(contains_word("auto & transport", "auto"));
+        assert!(!contains_word("carpet cleaning", "pet"));
+        assert!(!contains_word("competition fees", "pet"));
+        assert!(!contains_word("petrol", "pet"));
+        assert!(!contains_word("transportation", "sport"));
+        assert!(!contains_word("taxi", "tax"));
+    }
+}
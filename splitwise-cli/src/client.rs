@@ -0,0 +1,111 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use dialoguer::Password;
+use secrecy::SecretString;
+use splitwise::client::Cache;
+use splitwise::client::Client;
+use splitwise::credentials::CredentialStore;
+
+use crate::config::Config;
+use crate::config::Profile;
+
+/// Config profile selected with `--profile`, and its name.
+static PROFILE: OnceLock<(String, Profile)> = OnceLock::new();
+
+/// Whether `--no-cache` was given.
+static NO_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Stops `client` from caching responses on disk.
+pub(crate) fn disable_cache() {
+    NO_CACHE.store(true, Ordering::Relaxed);
+}
+
+/// Selects the config profile used by `client` and `profile`, or the
+/// default one.
+pub(crate) fn select_profile(name: Option<&str>) -> Result<()> {
+    let selected = Config::load()?.profile(name)?;
+    // Only the first selection counts, like the `--profile` flag
+    let _ = PROFILE.set(selected);
+    Ok(())
+}
+
+/// Name of the selected config profile.
+pub(crate) fn profile_name() -> &'static str {
+    &selected().0
+}
+
+fn selected() -> &'static (String, Profile) {
+    PROFILE.get_or_init(Default::default)
+}
+
+/// Defaults of the selected config profile.
+pub(crate) fn profile() -> &'static Profile {
+    &selected().1
+}
+
+/// Encrypted credential file of a profile, written by `login`.
+pub(crate) fn credential_store(profile: &str) -> Result<CredentialStore> {
+    let dir = Config::path()
+        .and_then(|p| p.parent().map(|dir| dir.to_path_buf()))
+        .ok_or_else(|| anyhow!("no config directory to keep credentials in"))?;
+    Ok(CredentialStore::new(
+        dir.join("credentials").join(format!("{}.json", profile)),
+    ))
+}
+
+/// Passphrase of the credential store, from `$SPLITWISE_PASSPHRASE` for
+/// scripts or else prompted for.
+fn passphrase() -> Result<SecretString> {
+    if let Ok(passphrase) = std::env::var("SPLITWISE_PASSPHRASE") {
+        return Ok(passphrase.into());
+    }
+    Ok(Password::new().with_prompt("Passphrase").interact()?.into())
+}
+
+/// Builds the Splitwise client shared by all commands, with the base URL and
+/// locale of the selected profile. The credential is the one in the profile,
+/// else the one saved by `login`, else `$SPLITWISE_API_KEY`. Unless disabled
+/// with `--no-cache`, responses from read endpoints that rarely change are
+/// cached on disk, so that they are shared between invocations. Groups and
+/// friends are not cached, since they carry balances that change elsewhere.
+pub(crate) fn client() -> Result<Client> {
+    let (name, profile) = selected();
+    let mut client = Client::default();
+    if let Some(ref api_key) = profile.api_key {
+        client = client.with_api_key(api_key.clone());
+    } else if let Some(ref token) = profile.oauth_token {
+        client = client.with_oauth_token(token.clone());
+    } else if let Some(store) = credential_store(name).ok().filter(|s| s.exists()) {
+        // Without a config dir there is no store, but the env may have a key
+        client = client.with_credential_store(&store, &passphrase()?)?;
+    }
+    if let Some(ref base_url) = profile.base_url {
+        client = client.with_base_url(base_url)?;
+    }
+    if let Some(ref locale) = profile.locale {
+        client = client.with_locale(locale);
+    }
+    if !client.has_credential() {
+        bail!(
+            "no Splitwise credential configured: run `splitwise login`, set api_key or oauth_token in profile '{}' of {}, or set SPLITWISE_API_KEY",
+            name,
+            Config::path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "the config file".to_string())
+        );
+    }
+
+    if let Some(dir) = dirs::cache_dir().filter(|_| !NO_CACHE.load(Ordering::Relaxed)) {
+        let cache = Cache::on_disk(dir.join("splitwise"))?
+            .with_ttl("get_groups", Duration::ZERO)
+            .with_ttl("get_friends", Duration::ZERO);
+        client = client.with_cache(cache);
+    }
+    Ok(client)
+}
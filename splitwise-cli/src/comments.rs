@@ -0,0 +1,77 @@
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use splitwise::model::comments::Comment;
+
+use crate::client::client;
+use crate::output::user_name;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    #[clap(flatten)]
+    output: OutputArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the comments on an expense
+    List {
+        /// Splitwise expense ID
+        expense_id: i64,
+    },
+    /// Comment on an expense
+    Add {
+        /// Splitwise expense ID
+        expense_id: i64,
+
+        /// Text of the comment
+        content: String,
+    },
+    /// Delete a comment
+    Delete {
+        /// Splitwise comment ID
+        id: i64,
+    },
+}
+
+pub(crate) async fn comments(args: Args) -> Result<()> {
+    let client = client()?;
+    let comments = client.comments();
+    let output = args.output;
+
+    match args.command {
+        Command::List { expense_id } => {
+            let list = comments.get_comments(expense_id).await?;
+            output.print(&list, |c| comments_table(c))
+        }
+        Command::Add {
+            expense_id,
+            content,
+        } => {
+            let comment = comments.create_comment(expense_id, content).await?;
+            output.print(&comment, |c| comments_table(std::slice::from_ref(c)))
+        }
+        Command::Delete { id } => {
+            let comment = comments.delete_comment(id).await?;
+            output.print(&comment, |c| comments_table(std::slice::from_ref(c)))
+        }
+    }
+}
+
+fn comments_table(comments: &[Comment]) -> Table {
+    let mut table = Table::new(&["ID", "Date", "Author", "Comment"]);
+    for comment in comments {
+        table.row(vec![
+            comment.id.to_string(),
+            comment.created_at.date_naive().to_string(),
+            comment.user.as_ref().map(user_name).unwrap_or_default(),
+            comment.content.clone(),
+        ]);
+    }
+    table
+}
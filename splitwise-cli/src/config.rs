@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use secrecy::SecretString;
+use serde::Deserialize;
+
+/// Name of the profile used when none is selected or set as default.
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+/// CLI configuration, read from `config.toml` in the XDG config directory,
+/// eg `~/.config/splitwise/config.toml`, or from `$SPLITWISE_CONFIG`:
+///
+/// ```toml
+/// default_profile = "home"
+///
+/// [profiles.home]
+/// api_key = "..."
+/// default_group = 123
+/// currency = "EUR"
+///
+/// [profiles.work]
+/// oauth_token = "..."
+/// base_url = "https://secure.splitwise.com/api/v3.0/"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Profile used when `--profile` is not given
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Account and defaults used by the commands.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    /// Splitwise API key
+    pub api_key: Option<SecretString>,
+    /// OAuth 2.0 access token, used if no API key is set
+    pub oauth_token: Option<SecretString>,
+    /// Splitwise API base URL, eg a mock server
+    pub base_url: Option<String>,
+    /// Group ID used by commands when none is given
+    pub default_group: Option<i64>,
+    /// Currency code of new expenses
+    pub currency: Option<String>,
+    /// Preferred locale, eg `en` or `pt-BR`
+    pub locale: Option<String>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        match std::env::var_os("SPLITWISE_CONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => dirs::config_dir().map(|dir| dir.join("splitwise").join("config.toml")),
+        }
+    }
+
+    /// Reads the config file, or an empty config if there is none.
+    pub fn load() -> Result<Self> {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("parsing config {}", path.display()))
+    }
+
+    /// Finds a profile by name, or the default one. A missing default profile
+    /// is empty, so the CLI works without a config file.
+    pub fn profile(&self, name: Option<&str>) -> Result<(String, Profile)> {
+        let explicit = name.or(self.default_profile.as_deref());
+        let name = explicit.unwrap_or(DEFAULT_PROFILE);
+        match self.profiles.get(name) {
+            Some(profile) => Ok((name.to_string(), profile.clone())),
+            None if explicit.is_none() => Ok((name.to_string(), Profile::default())),
+            None => Err(anyhow!(
+                "no profile named '{}' in {}",
+                name,
+                Self::path()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "the config".to_string())
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    #[test]
+    fn profile_selects_named_or_default_profile() {
+        let config: Config = toml::from_str(
+            r#"
+            default_profile = "home"
+
+            [profiles.home]
+            api_key = "home-key"
+            default_group = 7
+
+            [profiles.work]
+            oauth_token = "work-token"
+            currency = "EUR"
+            "#,
+        )
+        .unwrap();
+
+        let (name, home) = config.profile(None).unwrap();
+        assert_eq!(name, "home");
+        assert_eq!(home.api_key.unwrap().expose_secret(), "home-key");
+        assert_eq!(home.default_group, Some(7));
+
+        let (_, work) = config.profile(Some("work")).unwrap();
+        assert!(work.api_key.is_none());
+        assert_eq!(work.currency.as_deref(), Some("EUR"));
+
+        assert!(config.profile(Some("missing")).is_err());
+        assert!(Config::default().profile(None).is_ok());
+    }
+}
@@ -0,0 +1,591 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Result;
+use chrono::Days;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use clap::ArgEnum;
+use clap::Parser;
+use clap::Subcommand;
+use dialoguer::Confirm;
+use regex::RegexBuilder;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use splitwise::bulk::apply_bulk_operation;
+use splitwise::bulk::select_expenses;
+use splitwise::bulk::BulkOperation;
+use splitwise::bulk::ExpenseFilter;
+use splitwise::export::ExpenseExportOptions;
+use splitwise::model::expenses::CreateExpenseRequest;
+use splitwise::model::expenses::Expense;
+use splitwise::model::expenses::ListExpensesRequest;
+use splitwise::model::expenses::UpdateExpenseRequest;
+use splitwise::model::expenses::UserShare;
+
+use crate::client::client;
+use crate::client::profile;
+use crate::output::cell;
+use crate::output::user_name;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    #[clap(flatten)]
+    output: OutputArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List expenses, most recent first
+    List(ListArgs),
+    /// Show an expense with the shares of each user
+    Show {
+        /// Splitwise expense ID
+        id: i64,
+    },
+    /// Add an expense
+    Add(AddArgs),
+    /// Change fields of an expense
+    Edit(EditArgs),
+    /// Delete an expense
+    Delete {
+        /// Splitwise expense ID
+        id: i64,
+    },
+    /// Restore a deleted expense
+    Restore {
+        /// Splitwise expense ID
+        id: i64,
+    },
+    /// Move an expense to another group, keeping its shares
+    Move {
+        /// Splitwise expense ID
+        id: i64,
+
+        /// Splitwise group ID to move the expense to, or 0 for none
+        group_id: i64,
+
+        /// Add users of the expense missing from the group to it
+        #[clap(long)]
+        add_members: bool,
+    },
+    /// Export expenses with one row per expense and columns per member
+    Export(ExportArgs),
+    /// Change every expense matching a filter, after a preview
+    Bulk(BulkArgs),
+}
+
+#[derive(Parser)]
+struct ListArgs {
+    /// Only list expenses in this group. Defaults to the default group of
+    /// the profile, unless listing expenses with a friend.
+    #[clap(long)]
+    group_id: Option<i64>,
+
+    /// Only list expenses with this friend
+    #[clap(long)]
+    friend_id: Option<i64>,
+
+    /// Limit to expenses on or after this date
+    #[clap(long)]
+    after: Option<NaiveDate>,
+
+    /// Limit to expenses on or before this date
+    #[clap(long)]
+    before: Option<NaiveDate>,
+
+    /// Maximum number of expenses to list, or 0 for all
+    #[clap(long, default_value = "20")]
+    limit: i64,
+
+    /// Number of expenses to skip
+    #[clap(long, default_value = "0")]
+    offset: i64,
+}
+
+#[derive(Parser)]
+struct AddArgs {
+    /// Short description of the expense
+    description: String,
+
+    /// Total cost, eg `25.00`
+    cost: String,
+
+    /// Splitwise group ID to add the expense to, or 0 for none. Defaults to
+    /// the default group of the profile.
+    #[clap(long)]
+    group_id: Option<i64>,
+
+    /// Currency code of the cost. Defaults to the currency of the profile,
+    /// or USD.
+    #[clap(long)]
+    currency: Option<String>,
+
+    /// Splitwise category ID
+    #[clap(long, default_value = "0")]
+    category_id: i64,
+
+    /// Date of the expense. Defaults to today.
+    #[clap(long)]
+    date: Option<NaiveDate>,
+
+    /// Notes about the expense
+    #[clap(long)]
+    details: Option<String>,
+
+    /// Share of a user as `USER_ID:PAID:OWED`, eg `42:25.00:12.50`. The
+    /// expense is split equally within the group if no share is given.
+    #[clap(long = "share", parse(try_from_str = parse_share))]
+    shares: Vec<UserShare>,
+}
+
+#[derive(Parser)]
+struct EditArgs {
+    /// Splitwise expense ID
+    id: i64,
+
+    /// New description
+    #[clap(long)]
+    description: Option<String>,
+
+    /// New total cost. Shares must be given too unless they still add up.
+    #[clap(long)]
+    cost: Option<String>,
+
+    /// New currency code
+    #[clap(long)]
+    currency: Option<String>,
+
+    /// New Splitwise category ID
+    #[clap(long)]
+    category_id: Option<i64>,
+
+    /// New date
+    #[clap(long)]
+    date: Option<NaiveDate>,
+
+    /// New notes
+    #[clap(long)]
+    details: Option<String>,
+
+    /// New shares, as `USER_ID:PAID:OWED`, replacing all current shares
+    #[clap(long = "share", parse(try_from_str = parse_share))]
+    shares: Vec<UserShare>,
+}
+
+#[derive(Clone, Copy, ArgEnum)]
+pub(crate) enum Format {
+    Csv,
+}
+
+#[derive(Parser)]
+pub(crate) struct ExportArgs {
+    /// Output format
+    #[clap(long, arg_enum, default_value = "csv")]
+    format: Format,
+
+    /// Only export expenses in this group
+    #[clap(long)]
+    group_id: Option<i64>,
+
+    /// Limit to expenses on or after this date
+    #[clap(long)]
+    after: Option<NaiveDate>,
+
+    /// Limit to expenses on or before this date
+    #[clap(long)]
+    before: Option<NaiveDate>,
+
+    /// Also export deleted expenses
+    #[clap(long)]
+    include_deleted: bool,
+
+    /// Path to write the export to, instead of stdout
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct BulkArgs {
+    /// Only change expenses in this group, or 0 for expenses outside of groups
+    #[clap(long)]
+    group_id: Option<i64>,
+
+    /// Only change expenses on or after this date
+    #[clap(long)]
+    after: Option<NaiveDate>,
+
+    /// Only change expenses on or before this date
+    #[clap(long)]
+    before: Option<NaiveDate>,
+
+    /// Only change expenses whose description matches this regex,
+    /// ignoring case
+    #[clap(long)]
+    description: Option<String>,
+
+    /// Only change expenses in this Splitwise category
+    #[clap(long)]
+    category_id: Option<i64>,
+
+    /// Only change expenses paid, at least partly, by this user
+    #[clap(long)]
+    paid_by: Option<i64>,
+
+    /// Only change expenses costing at least this amount
+    #[clap(long)]
+    min_cost: Option<Decimal>,
+
+    /// Only change expenses costing at most this amount
+    #[clap(long)]
+    max_cost: Option<Decimal>,
+
+    /// Change every expense of the account when no filter is given
+    #[clap(long)]
+    all: bool,
+
+    /// Apply the change without asking for confirmation
+    #[clap(long)]
+    assume_yes: bool,
+
+    /// Only list the expenses that would change
+    #[clap(long)]
+    dry_run: bool,
+
+    #[clap(subcommand)]
+    operation: BulkCommand,
+}
+
+#[derive(Subcommand)]
+enum BulkCommand {
+    /// Delete the expenses
+    Delete,
+    /// Restore the deleted expenses
+    Restore,
+    /// Move the expenses to another category
+    Recategorize {
+        /// Splitwise category ID
+        category_id: i64,
+    },
+    /// Move the expenses to another group
+    Move {
+        /// Splitwise group ID, or 0 for none
+        group_id: i64,
+    },
+    /// Change the date of the expenses
+    SetDate {
+        /// New date
+        date: NaiveDate,
+    },
+    /// Change the currency of the expenses, keeping their cost
+    SetCurrency {
+        /// New currency code
+        currency: String,
+    },
+}
+
+/// Result of a bulk operation on one expense.
+#[derive(Serialize)]
+struct BulkResult {
+    id: i64,
+    description: Option<String>,
+    error: Option<String>,
+}
+
+pub(crate) async fn expenses(args: Args) -> Result<()> {
+    let output = args.output;
+    match args.command {
+        Command::List(args) => list(args, &output).await,
+        Command::Show { id } => {
+            let expense = client()?.expenses().get_expense(id).await?;
+            output.print(&expense, shares_table)
+        }
+        Command::Add(args) => add(args, &output).await,
+        Command::Edit(args) => edit(args, &output).await,
+        Command::Delete { id } => {
+            client()?.expenses().delete_expense(id).await?;
+            output.print(&serde_json::json!({ "id": id, "deleted": true }), |_| {
+                Table::fields(&[("Deleted expense", id.to_string())])
+            })
+        }
+        Command::Restore { id } => {
+            client()?.expenses().restore_expense(id).await?;
+            output.print(&serde_json::json!({ "id": id, "restored": true }), |_| {
+                Table::fields(&[("Restored expense", id.to_string())])
+            })
+        }
+        Command::Move {
+            id,
+            group_id,
+            add_members,
+        } => {
+            let moved = client()?
+                .expenses()
+                .move_expense(id, group_id, add_members)
+                .await?;
+            output.print(&moved, |e| expenses_table(e))
+        }
+        Command::Export(args) => export(args).await,
+        Command::Bulk(args) => bulk(args, &output).await,
+    }
+}
+
+async fn list(args: ListArgs, output: &OutputArgs) -> Result<()> {
+    let group_id = match args.friend_id {
+        Some(_) => args.group_id,
+        None => args.group_id.or(profile().default_group),
+    };
+    let request = ListExpensesRequest {
+        group_id,
+        friend_id: args.friend_id,
+        dated_after: args.after.map(midnight),
+        // The API bound is exclusive, so include the whole day
+        dated_before: args
+            .before
+            .and_then(|d| d.checked_add_days(Days::new(1)))
+            .map(midnight),
+        limit: Some(args.limit),
+        offset: Some(args.offset),
+        ..ListExpensesRequest::default()
+    };
+    let expenses = client()?.expenses().list_expenses(request).await?;
+    output.print(&expenses, |e| expenses_table(e))
+}
+
+async fn add(args: AddArgs, output: &OutputArgs) -> Result<()> {
+    let date = args.date.unwrap_or_else(|| Utc::now().date_naive());
+    let request = CreateExpenseRequest {
+        cost: args.cost,
+        description: args.description,
+        details: args.details,
+        date: midnight(date),
+        repeat_interval: "never".to_string(),
+        currency_code: args
+            .currency
+            .or_else(|| profile().currency.clone())
+            .unwrap_or_else(|| "USD".to_string()),
+        category_id: args.category_id,
+        group_id: args
+            .group_id
+            .or(profile().default_group)
+            .unwrap_or_default(),
+        split_equally: args.shares.is_empty(),
+        payment: None,
+        users: (!args.shares.is_empty()).then_some(args.shares),
+    };
+    let created = client()?.expenses().create_expense(request).await?;
+    output.print(&created, |e| expenses_table(e))
+}
+
+async fn edit(args: EditArgs, output: &OutputArgs) -> Result<()> {
+    let client = client()?;
+    let request = UpdateExpenseRequest {
+        cost: args.cost,
+        description: args.description,
+        details: args.details,
+        date: args.date.map(midnight),
+        currency_code: args.currency,
+        category_id: args.category_id,
+        users: (!args.shares.is_empty()).then_some(args.shares),
+        ..UpdateExpenseRequest::default()
+    };
+    let updated = client.expenses().update_expense(args.id, request).await?;
+    output.print(&updated, |e| expenses_table(e))
+}
+
+async fn export(args: ExportArgs) -> Result<()> {
+    let options = ExpenseExportOptions {
+        group_id: args.group_id,
+        dated_after: args.after.map(midnight),
+        // The API bound is exclusive, so include the whole day
+        dated_before: args
+            .before
+            .and_then(|d| d.checked_add_days(Days::new(1)))
+            .map(midnight),
+        include_deleted: args.include_deleted,
+    };
+
+    let writer: Box<dyn Write> = match args.output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+    let written = match args.format {
+        Format::Csv => splitwise::export::export_expenses_csv(&client()?, &options, writer).await?,
+    };
+    eprintln!("Exported {} expenses", written);
+    Ok(())
+}
+
+async fn bulk(args: BulkArgs, output: &OutputArgs) -> Result<()> {
+    let description = args
+        .description
+        .as_deref()
+        .map(|pattern| RegexBuilder::new(pattern).case_insensitive(true).build())
+        .transpose()?;
+    let filter = ExpenseFilter {
+        group_id: args.group_id,
+        dated_after: args.after.map(midnight),
+        // The API bound is exclusive, so include the whole day
+        dated_before: args
+            .before
+            .and_then(|d| d.checked_add_days(Days::new(1)))
+            .map(midnight),
+        description,
+        category_id: args.category_id,
+        paid_by: args.paid_by,
+        min_cost: args.min_cost,
+        max_cost: args.max_cost,
+    };
+    if filter.is_empty() && !args.all {
+        bail!("no filter given: pass filters, or --all to change every expense")
+    }
+    let operation = match args.operation {
+        BulkCommand::Delete => BulkOperation::Delete,
+        BulkCommand::Restore => BulkOperation::Restore,
+        BulkCommand::Recategorize { category_id } => BulkOperation::Recategorize(category_id),
+        BulkCommand::Move { group_id } => BulkOperation::Move(group_id),
+        BulkCommand::SetDate { date } => BulkOperation::SetDate(midnight(date)),
+        BulkCommand::SetCurrency { currency } => BulkOperation::SetCurrency(currency),
+    };
+
+    let client = client()?;
+    let selected = select_expenses(&client, &filter, &operation).await?;
+    if args.dry_run {
+        return output.print(&selected, |e| expenses_table(e));
+    }
+    if selected.is_empty() {
+        eprintln!("No expenses match");
+        return Ok(());
+    }
+    if !args.assume_yes {
+        expenses_table(&selected).write(&mut std::io::stderr().lock())?;
+        let confirmed = Confirm::new()
+            .with_prompt(format!("Apply to these {} expenses?", selected.len()))
+            .interact()?;
+        if !confirmed {
+            return Ok(());
+        }
+    }
+
+    let results: Vec<BulkResult> = apply_bulk_operation(&client, &selected, &operation)
+        .await
+        .into_iter()
+        .zip(selected.iter())
+        .map(|(result, expense)| BulkResult {
+            id: expense.id.unwrap_or_default(),
+            description: expense.description.clone(),
+            error: result.err().map(|e| e.to_string()),
+        })
+        .collect();
+    output.print(&results, |results| {
+        let mut table = Table::new(&["ID", "Description", "Result"]);
+        for result in results {
+            table.row(vec![
+                result.id.to_string(),
+                cell(result.description.as_ref()),
+                result.error.clone().unwrap_or_else(|| "ok".to_string()),
+            ]);
+        }
+        table
+    })?;
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    if failed > 0 {
+        bail!("{} of {} expenses failed", failed, results.len())
+    }
+    Ok(())
+}
+
+fn midnight(date: NaiveDate) -> chrono::DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// Parses a `USER_ID:PAID:OWED` share.
+fn parse_share(s: &str) -> Result<UserShare> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let [user_id, paid, owed] = parts.as_slice() else {
+        return Err(anyhow!("expected USER_ID:PAID:OWED, got '{}'", s));
+    };
+    Ok(UserShare {
+        user_id: Some(user_id.parse()?),
+        paid_share: Some(paid.parse::<Decimal>()?.to_string()),
+        owed_share: Some(owed.parse::<Decimal>()?.to_string()),
+        ..UserShare::default()
+    })
+}
+
+fn expenses_table(expenses: &[Expense]) -> Table {
+    let mut table = Table::new(&[
+        "ID",
+        "Date",
+        "Description",
+        "Cost",
+        "Currency",
+        "Group",
+        "Deleted",
+    ]);
+    for expense in expenses {
+        table.row(vec![
+            cell(expense.id),
+            cell(expense.date.map(|d| d.date_naive())),
+            cell(expense.description.as_ref()),
+            cell(expense.cost.as_ref()),
+            cell(expense.currency_code.as_ref()),
+            cell(expense.group_id),
+            if expense.deleted_at.is_some() {
+                "yes"
+            } else {
+                ""
+            }
+            .to_string(),
+        ]);
+    }
+    table
+}
+
+/// Shares of an expense, after a line describing it.
+fn shares_table(expense: &Expense) -> Table {
+    println!(
+        "{} {} {} on {}",
+        expense.description.as_deref().unwrap_or_default(),
+        expense.cost.as_deref().unwrap_or_default(),
+        expense.currency_code.as_deref().unwrap_or_default(),
+        cell(expense.date.map(|d| d.date_naive()))
+    );
+    let mut table = Table::new(&["User ID", "Name", "Paid", "Owed", "Net"]);
+    for share in expense.users.iter().flatten() {
+        table.row(vec![
+            cell(share.user_id()),
+            share.user.as_ref().map(user_name).unwrap_or_default(),
+            cell(share.paid_share.as_ref()),
+            cell(share.owed_share.as_ref()),
+            cell(share.net_balance.as_ref()),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_share_reads_user_paid_and_owed() {
+        let share = parse_share("42:25.00:12.5").unwrap();
+        assert_eq!(share.user_id, Some(42));
+        assert_eq!(share.paid_share.as_deref(), Some("25.00"));
+        assert_eq!(share.owed_share.as_deref(), Some("12.5"));
+        assert!(parse_share("42:25.00").is_err());
+        assert!(parse_share("me:1:1").is_err());
+    }
+}
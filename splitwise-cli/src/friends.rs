@@ -0,0 +1,94 @@
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use splitwise::model::friends::AddFriendsRequest;
+use splitwise::model::users::User;
+
+use crate::client::client;
+use crate::output::balances;
+use crate::output::cell;
+use crate::output::check_success;
+use crate::output::user_name;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    #[clap(flatten)]
+    output: OutputArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List friends with their balances
+    List,
+    /// Add friends by email, inviting those without an account
+    Add {
+        /// Emails of the friends to add
+        #[clap(required = true)]
+        emails: Vec<String>,
+
+        /// Message included in invitation emails
+        #[clap(long)]
+        message: Option<String>,
+    },
+    /// Remove a friend
+    Remove {
+        /// Splitwise user ID of the friend
+        id: i64,
+    },
+}
+
+pub(crate) async fn friends(args: Args) -> Result<()> {
+    let client = client()?;
+    let friends = client.friends();
+    let output = args.output;
+
+    match args.command {
+        Command::List => output.print(&friends.list_friends().await?, |f| friends_table(f)),
+        Command::Add { emails, message } => {
+            let response = friends
+                .add_friends(AddFriendsRequest {
+                    emails,
+                    message,
+                    allow_partial_success: Some(true),
+                })
+                .await?;
+            for (email, errors) in response.errors.iter().flatten() {
+                eprintln!("Failed adding {}: {}", email, errors.join("; "));
+            }
+            output.print(&response, |response| {
+                friends_table(response.users.as_deref().unwrap_or_default())
+            })
+        }
+        Command::Remove { id } => {
+            let response = friends.delete_friend(id).await?;
+            let errors = response
+                .errors
+                .iter()
+                .flatten()
+                .flat_map(|(_, e)| e.iter().cloned())
+                .collect();
+            check_success(response.success, errors, "remove friend")?;
+            output.print(&response, |_| {
+                Table::fields(&[("Removed friend", id.to_string())])
+            })
+        }
+    }
+}
+
+fn friends_table(friends: &[User]) -> Table {
+    let mut table = Table::new(&["ID", "Name", "Email", "Balance"]);
+    for friend in friends {
+        table.row(vec![
+            cell(friend.id),
+            user_name(friend),
+            cell(friend.email.as_ref()),
+            balances(friend.balance.as_ref()),
+        ]);
+    }
+    table
+}
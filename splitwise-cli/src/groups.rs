@@ -0,0 +1,292 @@
+use anyhow::bail;
+use anyhow::Result;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use clap::Parser;
+use clap::Subcommand;
+use splitwise::model::groups::Group;
+use splitwise::model::groups::GroupCreateRequest;
+use splitwise::model::groups::GroupUser;
+use splitwise::rollover::rollover;
+use splitwise::rollover::RolloverOptions;
+
+use crate::client::client;
+use crate::output::balances;
+use crate::output::cell;
+use crate::output::check_success;
+use crate::output::user_name;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    #[clap(flatten)]
+    output: OutputArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the groups of the current user
+    List,
+    /// Show the members and debts of a group
+    Show {
+        /// Splitwise group ID
+        id: i64,
+    },
+    /// Create a group
+    Create {
+        /// Name of the group
+        name: String,
+
+        /// Type of the group, eg `apartment`, `house`, `trip` or `other`
+        #[clap(long = "type")]
+        group_type: Option<String>,
+
+        /// Simplify debts between members by default
+        #[clap(long)]
+        simplify: bool,
+
+        /// IDs of users to add to the group
+        #[clap(long = "user-id")]
+        user_ids: Vec<i64>,
+    },
+    /// Delete a group and its expenses
+    Delete {
+        /// Splitwise group ID
+        id: i64,
+    },
+    /// Restore a deleted group
+    Restore {
+        /// Splitwise group ID
+        id: i64,
+    },
+    /// Add a user to a group, by ID or by email
+    AddMember {
+        /// Splitwise group ID
+        group_id: i64,
+
+        #[clap(flatten)]
+        user: UserArgs,
+    },
+    /// Remove a user with a settled balance from a group
+    RemoveMember {
+        /// Splitwise group ID
+        group_id: i64,
+
+        /// ID of the user to remove
+        user_id: i64,
+    },
+    /// Start a new group with the members, settings and balances of a group
+    Rollover {
+        /// Splitwise group ID
+        id: i64,
+
+        /// Name of the new group
+        name: String,
+
+        /// Date of the opening balances. Defaults to today.
+        #[clap(long)]
+        date: Option<NaiveDate>,
+
+        /// Also settle all balances of the old group
+        #[clap(long)]
+        settle: bool,
+    },
+}
+
+#[derive(Parser)]
+struct UserArgs {
+    /// ID of an existing user
+    #[clap(long, required_unless_present = "email")]
+    user_id: Option<i64>,
+
+    /// Email of the user, who is invited if they have no account
+    #[clap(long, conflicts_with = "user-id")]
+    email: Option<String>,
+
+    /// First name of an invited user
+    #[clap(long, requires = "email")]
+    first_name: Option<String>,
+
+    /// Last name of an invited user
+    #[clap(long, requires = "email")]
+    last_name: Option<String>,
+}
+
+pub(crate) async fn groups(args: Args) -> Result<()> {
+    let client = client()?;
+    let groups = client.groups();
+    let output = args.output;
+
+    match args.command {
+        Command::List => output.print(&groups.list_groups().await?, |g| groups_table(g)),
+        Command::Show { id } => output.print(&groups.get_group(id).await?, |group| {
+            println!(
+                "{} (group {})",
+                group.name.as_deref().unwrap_or_default(),
+                group.id.unwrap_or_default()
+            );
+            group_table(group)
+        }),
+        Command::Create {
+            name,
+            group_type,
+            simplify,
+            user_ids,
+        } => {
+            let users = user_ids
+                .into_iter()
+                .map(|id| GroupUser {
+                    user_id: Some(id),
+                    ..GroupUser::default()
+                })
+                .collect::<Vec<_>>();
+            let request = GroupCreateRequest {
+                name,
+                group_type,
+                simplify_by_default: Some(simplify),
+                users: (!users.is_empty()).then_some(users),
+            };
+            let group = groups.create_group(request).await?;
+            output.print(&group, |group| groups_table(std::slice::from_ref(group)))
+        }
+        Command::Delete { id } => {
+            let response = groups.delete_group(id).await?;
+            check_success(response.success, Vec::new(), "delete group")?;
+            output.print(&response, |_| {
+                Table::fields(&[("Deleted group", id.to_string())])
+            })
+        }
+        Command::Restore { id } => {
+            let response = groups.restore_group(id).await?;
+            let errors = response.errors.clone().unwrap_or_default();
+            check_success(response.success, errors, "restore group")?;
+            output.print(&response, |_| {
+                Table::fields(&[("Restored group", id.to_string())])
+            })
+        }
+        Command::AddMember { group_id, user } => {
+            let user = GroupUser {
+                user_id: user.user_id,
+                first_name: user.first_name,
+                last_name: user.last_name,
+                email: user.email,
+            };
+            let response = groups.add_user_to_group(group_id, user).await?;
+            let errors = response
+                .errors
+                .iter()
+                .flatten()
+                .flat_map(|(_, e)| e.iter().cloned())
+                .collect();
+            check_success(response.success, errors, "add member")?;
+            output.print(&response, |response| {
+                let mut table = Table::new(&["ID", "Name", "Email"]);
+                if let Some(user) = response.user.as_ref() {
+                    table.row(vec![
+                        cell(user.id),
+                        user_name(user),
+                        cell(user.email.as_ref()),
+                    ]);
+                }
+                table
+            })
+        }
+        Command::RemoveMember { group_id, user_id } => {
+            let response = groups.remove_user_from_group(group_id, user_id).await?;
+            let errors = response.errors.clone().unwrap_or_default();
+            check_success(response.success, errors, "remove member")?;
+            output.print(&response, |_| {
+                Table::fields(&[("Removed user", user_id.to_string())])
+            })
+        }
+        Command::Rollover {
+            id,
+            name,
+            date,
+            settle,
+        } => {
+            let date = date.unwrap_or_else(|| Utc::now().date_naive());
+            let options = RolloverOptions {
+                name,
+                date: Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()),
+                settle_old_group: settle,
+            };
+            let report = rollover(&client, id, &options).await?;
+            output.print(&report, |report| {
+                Table::fields(&[
+                    ("New group", cell(report.group.id)),
+                    ("Name", cell(report.group.name.as_ref())),
+                    (
+                        "Opening balances",
+                        report.opening_balances.len().to_string(),
+                    ),
+                    ("Settlements", report.settlements.len().to_string()),
+                ])
+            })?;
+            if !report.errors.is_empty() {
+                bail!(
+                    "rollover incomplete, failed to create:\n{}",
+                    report.errors.join("\n")
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn groups_table(groups: &[Group]) -> Table {
+    let mut table = Table::new(&["ID", "Name", "Type", "Members", "Updated"]);
+    for group in groups {
+        table.row(vec![
+            cell(group.id),
+            cell(group.name.as_ref()),
+            cell(group.group_type.as_ref()),
+            cell(group.members.as_ref().map(|m| m.len())),
+            cell(group.updated_at.map(|d| d.date_naive())),
+        ]);
+    }
+    table
+}
+
+/// Members of a group with their balances and who they owe.
+fn group_table(group: &Group) -> Table {
+    let members = group.members.iter().flatten();
+    let name = |id: Option<i64>| {
+        members
+            .clone()
+            .find(|m| m.id == id)
+            .map(user_name)
+            .unwrap_or_else(|| cell(id))
+    };
+
+    let mut table = Table::new(&["ID", "Member", "Balance", "Owes"]);
+    for member in members.clone() {
+        let owes = group
+            .simplified_debts
+            .iter()
+            .flatten()
+            .filter(|d| d.from == member.id)
+            .map(|d| {
+                format!(
+                    "{} {} to {}",
+                    d.amount.as_deref().unwrap_or_default(),
+                    d.currency_code.as_deref().unwrap_or_default(),
+                    name(d.to)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        table.row(vec![
+            cell(member.id),
+            user_name(member),
+            balances(member.balance.as_ref()),
+            owes,
+        ]);
+    }
+    table
+}
@@ -0,0 +1,80 @@
+use anyhow::bail;
+use anyhow::Result;
+use clap::Parser;
+use dialoguer::Password;
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+use splitwise::client::Client;
+use splitwise::credentials::Credential;
+
+use crate::client::credential_store;
+use crate::client::profile;
+use crate::client::profile_name;
+use crate::output::user_name;
+
+#[derive(Parser)]
+pub(crate) struct LoginArgs {
+    /// Store an OAuth 2.0 access token instead of an API key
+    #[clap(long)]
+    oauth: bool,
+
+    /// Store the credential without checking it with Splitwise first
+    #[clap(long)]
+    no_verify: bool,
+}
+
+/// Prompts for a credential and a passphrase, and saves the credential
+/// encrypted for the selected profile. Nothing is read from the command line,
+/// so that credentials stay out of shell history.
+pub(crate) async fn login(args: LoginArgs) -> Result<()> {
+    let kind = if args.oauth { "OAuth token" } else { "API key" };
+    let secret: SecretString = Password::new().with_prompt(kind).interact()?.into();
+    let credential = if args.oauth {
+        Credential::OAuthToken(secret)
+    } else {
+        Credential::ApiKey(secret)
+    };
+
+    if !args.no_verify {
+        let mut client = match credential.clone() {
+            Credential::ApiKey(key) => Client::default().with_api_key(key),
+            Credential::OAuthToken(token) => Client::default().with_oauth_token(token),
+        };
+        if let Some(ref base_url) = profile().base_url {
+            client = client.with_base_url(base_url)?;
+        }
+        let user = client.users().get_current_user().await?;
+        println!("Authenticated as {}", user_name(&user));
+    }
+
+    let passphrase: SecretString = Password::new()
+        .with_prompt("New passphrase")
+        .with_confirmation("Repeat passphrase", "Passphrases do not match")
+        .interact()?
+        .into();
+    if passphrase.expose_secret().is_empty() {
+        bail!("the passphrase cannot be empty");
+    }
+
+    let store = credential_store(profile_name())?;
+    store.save(&credential, &passphrase)?;
+    println!(
+        "Saved {} for profile '{}' to {}",
+        kind,
+        profile_name(),
+        store.path().display()
+    );
+    Ok(())
+}
+
+/// Wipes the saved credential of the selected profile.
+pub(crate) async fn logout() -> Result<()> {
+    let store = credential_store(profile_name())?;
+    if !store.exists() {
+        println!("No saved credential for profile '{}'", profile_name());
+        return Ok(());
+    }
+    store.wipe()?;
+    println!("Removed saved credential for profile '{}'", profile_name());
+    Ok(())
+}
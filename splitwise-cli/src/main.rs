@@ -1,24 +1,132 @@
+mod add;
+mod archive;
+mod categories;
+mod client;
+mod comments;
+mod config;
+mod expenses;
+mod friends;
+mod groups;
+mod login;
+mod matcher;
 mod mint;
+mod notifications;
+mod ofx;
+mod output;
+mod plan;
+mod reconcile;
+mod recurring;
+mod refunds;
+mod rules;
+mod statement;
 mod sync;
+mod transaction;
+mod tui;
+mod users;
 
 use anyhow::Result;
 use clap::Parser;
+use clap::Subcommand;
+use tracing_subscriber::EnvFilter;
 
+use crate::add::add;
+use crate::archive::export;
+use crate::archive::import;
+use crate::client::disable_cache;
+use crate::client::select_profile;
+use crate::comments::comments;
+use crate::expenses::expenses;
+use crate::friends::friends;
+use crate::groups::groups;
+use crate::login::login;
+use crate::login::logout;
+use crate::notifications::notifications;
+use crate::reconcile::reconcile;
+use crate::recurring::recurring;
 use crate::sync::sync;
+use crate::tui::tui;
+use crate::users::me;
 
 /// Splitwise CLI
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
-enum Cli {
+struct Cli {
+    /// Config profile to use, instead of the default one
+    #[clap(long, global = true)]
+    profile: Option<String>,
+
+    /// Always fetch fresh data from Splitwise, without the on-disk cache
+    #[clap(long, global = true)]
+    no_cache: bool,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add an expense described in natural language
+    Add(add::Args),
+    /// Work with the comments on expenses
+    Comments(comments::Args),
+    /// Work with expenses
+    Expenses(expenses::Args),
+    /// Export the whole account into a JSON archive
+    Export(archive::ExportArgs),
+    /// Work with friends
+    Friends(friends::Args),
+    /// Work with groups and their members
+    Groups(groups::Args),
+    /// Recreate groups and expenses from a JSON archive
+    Import(archive::ImportArgs),
+    /// Save a credential for the profile, encrypted with a passphrase
+    Login(login::LoginArgs),
+    /// Remove the saved credential of the profile
+    Logout,
+    /// Show the current user
+    Me(users::MeArgs),
+    /// Show recent activity
+    Notifications(notifications::Args),
+    /// Compare a bank statement with the expenses of a Splitwise group
+    Reconcile(reconcile::Args),
+    /// Work with recurring expenses and their reminders
+    Recurring(recurring::Args),
+    /// Sync bank transactions to a Splitwise group
     Sync(sync::Args),
+    /// Browse groups and expenses in the terminal
+    Tui,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Only errors are logged unless enabled with eg `RUST_LOG=splitwise=debug`
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
     let cli = Cli::parse();
+    select_profile(cli.profile.as_deref())?;
+    if cli.no_cache {
+        disable_cache();
+    }
 
-    match cli {
-        Cli::Sync(args) => sync(args).await?,
+    match cli.command {
+        Command::Add(args) => add(args).await?,
+        Command::Comments(args) => comments(args).await?,
+        Command::Expenses(args) => expenses(args).await?,
+        Command::Export(args) => export(args).await?,
+        Command::Friends(args) => friends(args).await?,
+        Command::Groups(args) => groups(args).await?,
+        Command::Import(args) => import(args).await?,
+        Command::Login(args) => login(args).await?,
+        Command::Logout => logout().await?,
+        Command::Me(args) => me(args).await?,
+        Command::Notifications(args) => notifications(args).await?,
+        Command::Reconcile(args) => reconcile(args).await?,
+        Command::Recurring(args) => recurring(args).await?,
+        Command::Sync(args) => sync(args).await?,
+        Command::Tui => tui().await?,
     };
 
     Ok(())
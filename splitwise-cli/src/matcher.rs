@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use chrono::Duration;
+use chrono::NaiveDate;
+use clap::Parser;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use splitwise::model::expenses::Expense;
+
+use crate::transaction::Transaction;
+
+/// Largest accepted `--match-days`, so that the date window stays small.
+const MAX_MATCH_DAYS: i64 = 31;
+
+/// Source prefixes of the transaction IDs stored in expense details.
+const EXTERNAL_ID_PREFIXES: &[&str] = &["mint:", "csv:", "ofx:"];
+
+/// How close an expense must be to a transaction to be considered the same.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MatchOptions {
+    /// Maximum number of days between the transaction and expense dates.
+    pub days_tolerance: i64,
+    /// Maximum difference between the transaction amount and expense cost.
+    pub amount_tolerance: Decimal,
+    /// Minimum score, from 0 to 1, of a confident match. Expenses scoring
+    /// lower with the exact amount and date still make the transaction
+    /// ambiguous, so that it is never synced again as new.
+    pub min_score: f64,
+    /// Matches scoring within this margin of the best one make it ambiguous.
+    pub ambiguity_margin: f64,
+}
+
+/// Command line flags setting the match options.
+#[derive(Parser)]
+pub(crate) struct MatchArgs {
+    /// Maximum number of days between a transaction and a matching expense,
+    /// up to 31
+    #[clap(long, default_value = "1", parse(try_from_str = parse_match_days))]
+    match_days: i64,
+
+    /// Maximum difference between a transaction amount and a matching expense
+    #[clap(long, default_value = "1.00")]
+    match_amount: Decimal,
+
+    /// Minimum similarity score, from 0 to 1, of a matching expense
+    #[clap(long, default_value = "0.5")]
+    match_threshold: f64,
+}
+
+impl MatchArgs {
+    pub fn options(&self) -> MatchOptions {
+        MatchOptions {
+            days_tolerance: self.match_days,
+            amount_tolerance: self.match_amount,
+            min_score: self.match_threshold,
+            ..MatchOptions::default()
+        }
+    }
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            days_tolerance: 1,
+            amount_tolerance: Decimal::ONE,
+            min_score: 0.5,
+            ambiguity_margin: 0.1,
+        }
+    }
+}
+
+/// Existing expense a transaction was matched to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Match<'e> {
+    /// The expense details hold the transaction ID.
+    External(&'e Expense),
+    /// The expense is similar enough to the transaction, with its score.
+    Fuzzy(&'e Expense, f64),
+    /// Several expenses are about as similar to the transaction, best first.
+    Ambiguous(Vec<(&'e Expense, f64)>),
+    /// No expense is similar enough.
+    None,
+}
+
+/// Index of existing expenses, for finding the ones a transaction was already
+/// synced to.
+pub(crate) struct Matcher<'e> {
+    options: MatchOptions,
+    by_external_id: HashMap<&'e str, &'e Expense>,
+    by_date: HashMap<NaiveDate, Vec<&'e Expense>>,
+}
+
+impl<'e> Matcher<'e> {
+    pub fn new(expenses: &'e [Expense], options: MatchOptions) -> Self {
+        let mut by_external_id = HashMap::new();
+        let mut by_date: HashMap<NaiveDate, Vec<&Expense>> = HashMap::new();
+        for expense in expenses {
+            for id in external_ids(expense) {
+                by_external_id.insert(id, expense);
+            }
+            if let Some(date) = expense.date {
+                by_date.entry(date.date_naive()).or_default().push(expense);
+            }
+        }
+        Self {
+            options,
+            by_external_id,
+            by_date,
+        }
+    }
+
+    /// Finds the expense whose details hold the transaction ID, if any.
+    pub fn find_external(&self, id: &str) -> Option<&'e Expense> {
+        self.by_external_id.get(id).copied()
+    }
+
+    /// Finds the expense a transaction was synced to, if any.
+    pub fn find(&self, txn: &Transaction) -> Match<'e> {
+        if let Some(expense) = self.find_external(&txn.id) {
+            return Match::External(expense);
+        }
+
+        let tolerance = self.options.days_tolerance;
+        let mut candidates: Vec<(&Expense, f64)> = (-tolerance..=tolerance)
+            .flat_map(|days| {
+                self.by_date
+                    .get(&(txn.date + Duration::days(days)))
+                    .into_iter()
+                    .flatten()
+            })
+            .filter_map(|expense| Some((*expense, self.score(txn, expense)?)))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // Expenses with the same amount and date, but too different a
+        // description, may still be the same purchase
+        if candidates
+            .first()
+            .is_some_and(|(_, best)| *best < self.options.min_score)
+        {
+            candidates.retain(|(expense, _)| same_amount_and_date(txn, expense));
+            if candidates.is_empty() {
+                return Match::None;
+            }
+            return Match::Ambiguous(candidates);
+        }
+        candidates.retain(|(_, score)| *score >= self.options.min_score);
+
+        match candidates.as_slice() {
+            [] => Match::None,
+            [(expense, score)] => Match::Fuzzy(expense, *score),
+            [(expense, best), (_, second), ..] => {
+                if best - second > self.options.ambiguity_margin {
+                    Match::Fuzzy(expense, *best)
+                } else {
+                    let cutoff = best - self.options.ambiguity_margin;
+                    candidates.retain(|(_, score)| *score >= cutoff);
+                    Match::Ambiguous(candidates)
+                }
+            }
+        }
+    }
+
+    /// Scores how similar an expense is to a transaction, from 0 to 1, or
+    /// `None` if its amount or date is out of tolerance.
+    fn score(&self, txn: &Transaction, expense: &Expense) -> Option<f64> {
+        let cost = Decimal::from_str(expense.cost.as_deref()?).ok()?;
+        let amount_delta = (txn.amount - cost).abs();
+        if amount_delta > self.options.amount_tolerance {
+            return None;
+        }
+        let days_delta = (txn.date - expense.date?.date_naive()).num_days().abs();
+        if days_delta > self.options.days_tolerance {
+            return None;
+        }
+
+        let amount_score = if self.options.amount_tolerance.is_zero() {
+            1.0
+        } else {
+            1.0 - (amount_delta / self.options.amount_tolerance).to_f64()?
+        };
+        let date_score = 1.0 - days_delta as f64 / (self.options.days_tolerance + 1) as f64;
+        let description_score = similarity(
+            &txn.description,
+            expense.description.as_deref().unwrap_or_default(),
+        );
+        Some(0.3 * amount_score + 0.2 * date_score + 0.5 * description_score)
+    }
+}
+
+/// Whether an expense has exactly the amount and date of a transaction.
+fn same_amount_and_date(txn: &Transaction, expense: &Expense) -> bool {
+    let cost = expense
+        .cost
+        .as_deref()
+        .and_then(|c| Decimal::from_str(c).ok());
+    cost == Some(txn.amount) && expense.date.map(|d| d.date_naive()) == Some(txn.date)
+}
+
+/// Parses `--match-days`, which must be between 0 and `MAX_MATCH_DAYS`.
+fn parse_match_days(s: &str) -> anyhow::Result<i64> {
+    let days: i64 = s.parse()?;
+    if !(0..=MAX_MATCH_DAYS).contains(&days) {
+        anyhow::bail!("expected 0 to {} days, got {}", MAX_MATCH_DAYS, days)
+    }
+    Ok(days)
+}
+
+/// Transaction IDs stored in the details of an expense.
+fn external_ids(expense: &Expense) -> impl Iterator<Item = &str> {
+    expense
+        .details
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|word| EXTERNAL_ID_PREFIXES.iter().any(|p| word.starts_with(p)))
+}
+
+/// Sørensen–Dice similarity of the letter pairs of two descriptions, from 0
+/// to 1, ignoring case and punctuation.
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+    let bigrams = |s: &str| -> HashSet<(char, char)> {
+        let words: Vec<String> = s
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_string())
+            .collect();
+        words
+            .iter()
+            .flat_map(|w| {
+                let chars: Vec<char> = w.chars().collect();
+                chars.windows(2).map(|p| (p[0], p[1])).collect::<Vec<_>>()
+            })
+            .collect()
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn transaction(description: &str, amount: i64, day: u32) -> Transaction {
+        Transaction {
+            id: format!("csv:{}", description),
+            date: NaiveDate::from_ymd_opt(2023, 3, day).unwrap(),
+            amount: Decimal::new(amount, 2),
+            currency: "USD".to_string(),
+            account: "Checking".to_string(),
+            description: description.to_string(),
+            category: None,
+            category_parent: None,
+        }
+    }
+
+    #[test]
+    fn find_prefers_external_id_then_description() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {"id": 1, "date": "2023-03-01T00:00:00Z", "cost": "5.00", "description": "Other", "details": "note ofx:42"},
+            {"id": 2, "date": "2023-03-02T00:00:00Z", "cost": "42.10", "description": "Whole Foods"},
+            {"id": 3, "date": "2023-03-02T00:00:00Z", "cost": "42.00", "description": "Gas station"}
+        ]))
+        .unwrap();
+        let matcher = Matcher::new(&expenses, MatchOptions::default());
+
+        let mut txn = transaction("WHOLE FOODS MARKET", 4210, 2);
+        txn.id = "ofx:42".to_string();
+        assert!(matches!(matcher.find(&txn), Match::External(e) if e.id == Some(1)));
+
+        let txn = transaction("WHOLE FOODS MARKET", 4210, 3);
+        assert!(matches!(matcher.find(&txn), Match::Fuzzy(e, _) if e.id == Some(2)));
+
+        let txn = transaction("Cinema", 4210, 20);
+        assert_eq!(matcher.find(&txn), Match::None);
+    }
+
+    #[test]
+    fn find_reports_ambiguous_matches() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {"id": 1, "date": "2023-03-02T00:00:00Z", "cost": "10.00", "description": "Coffee"},
+            {"id": 2, "date": "2023-03-02T00:00:00Z", "cost": "10.00", "description": "Coffee"}
+        ]))
+        .unwrap();
+        let matcher = Matcher::new(&expenses, MatchOptions::default());
+
+        match matcher.find(&transaction("Coffee", 1000, 2)) {
+            Match::Ambiguous(candidates) => assert_eq!(candidates.len(), 2),
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn find_keeps_same_amount_and_date_with_other_description() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {"id": 1, "date": "2023-03-02T00:00:00Z", "cost": "23.40", "description": "Dinner with Sam"}
+        ]))
+        .unwrap();
+        let options = MatchOptions {
+            min_score: 0.6,
+            ..MatchOptions::default()
+        };
+        let matcher = Matcher::new(&expenses, options);
+
+        match matcher.find(&transaction("SQ *BISTRO 4411", 2340, 2)) {
+            Match::Ambiguous(candidates) => assert_eq!(candidates[0].0.id, Some(1)),
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+        assert_eq!(
+            matcher.find(&transaction("SQ *BISTRO 4411", 2340, 3)),
+            Match::None
+        );
+        assert!(parse_match_days("7").is_ok());
+        assert!(parse_match_days("-1").is_err());
+        assert!(parse_match_days("100000000000000").is_err());
+    }
+
+    #[test]
+    fn find_ignores_other_merchant_with_similar_amount() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {"id": 1, "date": "2023-03-02T00:00:00Z", "cost": "4.50", "description": "Coffee"}
+        ]))
+        .unwrap();
+        let matcher = Matcher::new(&expenses, MatchOptions::default());
+
+        let txn = transaction("BAKERY ON MAIN ST", 420, 2);
+        assert_eq!(matcher.find(&txn), Match::None);
+    }
+}
@@ -1,9 +1,59 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::Result;
 use chrono::DateTime;
 use chrono::NaiveDate;
 use chrono::Utc;
+use rust_decimal::Decimal;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::transaction::Transaction;
+use crate::transaction::TransactionSource;
+
+/// Transactions exported from Mint as JSON.
+pub(crate) struct MintSource {
+    path: PathBuf,
+}
+
+impl MintSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl TransactionSource for MintSource {
+    fn transactions(&self) -> Result<Vec<Transaction>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let transactions: Vec<MintTransaction> = serde_json::from_reader(reader)?;
+        transactions
+            .into_iter()
+            .map(Transaction::try_from)
+            .collect()
+    }
+}
+
+impl TryFrom<MintTransaction> for Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(txn: MintTransaction) -> Result<Self> {
+        // Mint amounts are floats with cents precision, and negative for expenses
+        let amount = Decimal::try_from(-txn.amount)?.round_dp(2);
+        Ok(Self {
+            id: format!("mint:{}", txn.id),
+            date: txn.date,
+            amount,
+            currency: txn.principal_currency.unwrap_or_else(|| "USD".to_string()),
+            account: txn.account_ref.name,
+            description: txn.description,
+            category: Some(txn.category.name).filter(|c| !c.is_empty()),
+            category_parent: Some(txn.category.parent_name).filter(|c| !c.is_empty()),
+        })
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MintTransaction {
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use clap::Parser;
+use regex::Regex;
+use splitwise::model::notifications::GetNotificationsRequest;
+use splitwise::model::notifications::Notification;
+
+use crate::client::client;
+use crate::output::cell;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    /// Only show notifications updated after this date
+    #[clap(long)]
+    after: Option<NaiveDate>,
+
+    /// Maximum number of notifications to show, or 0 for all
+    #[clap(long, default_value = "20")]
+    limit: i64,
+
+    #[clap(flatten)]
+    output: OutputArgs,
+}
+
+pub(crate) async fn notifications(args: Args) -> Result<()> {
+    let request = GetNotificationsRequest {
+        updated_after: args
+            .after
+            .map(|d| Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0).unwrap())),
+        limit: Some(args.limit),
+    };
+    let notifications = client()?.notifications().get_notifications(request).await?;
+    args.output
+        .print(&notifications, |n| notifications_table(n))
+}
+
+fn notifications_table(notifications: &[Notification]) -> Table {
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    let mut table = Table::new(&["ID", "Date", "Type", "Content"]);
+    for notification in notifications {
+        let content = notification.content.as_deref().unwrap_or_default();
+        table.row(vec![
+            cell(notification.id),
+            cell(notification.created_at.map(|d| d.date_naive())),
+            cell(
+                notification
+                    .notification_type
+                    .as_ref()
+                    .map(|t| format!("{:?}", t)),
+            ),
+            tags.replace_all(content, " ")
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,284 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::NaiveDate;
+use regex::Regex;
+use rust_decimal::Decimal;
+
+use crate::statement::fnv1a;
+use crate::transaction::Transaction;
+use crate::transaction::TransactionSource;
+
+/// Transactions from an OFX or QFX statement file.
+pub(crate) struct OfxSource {
+    path: PathBuf,
+}
+
+impl OfxSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl TransactionSource for OfxSource {
+    fn transactions(&self) -> Result<Vec<Transaction>> {
+        // Older statements are often Latin-1, so don't insist on UTF-8
+        let bytes = std::fs::read(&self.path)?;
+        parse_ofx_transactions(&String::from_utf8_lossy(&bytes))
+    }
+}
+
+/// Fields of a `STMTTRN` aggregate.
+#[derive(Default)]
+struct StatementTransaction {
+    fitid: Option<String>,
+    dtposted: Option<String>,
+    trnamt: Option<String>,
+    name: Option<String>,
+    memo: Option<String>,
+}
+
+/// Parses the bank and credit card transactions of an OFX statement. Both
+/// OFX 1.x SGML, where elements holding values have no end tags, and OFX 2.x
+/// XML are supported.
+///
+/// A `FITID` is only unique within an account, so transaction IDs look like
+/// `ofx:HASH:FITID`, where the hash is of the bank and account IDs. IDs end up
+/// in expense details, which group members can see, so account numbers are
+/// never written out in full.
+pub(crate) fn parse_ofx_transactions(statement: &str) -> Result<Vec<Transaction>> {
+    let start = statement
+        .find("<OFX>")
+        .ok_or_else(|| anyhow!("statement has no <OFX> element"))?;
+    let tag = Regex::new(r"<(/?)([A-Za-z0-9.]+)[^>]*>([^<]*)").unwrap();
+
+    let mut txns = Vec::new();
+    let mut bank = String::new();
+    let mut account = String::new();
+    let mut currency = "USD".to_string();
+    let mut current: Option<StatementTransaction> = None;
+    for captures in tag.captures_iter(&statement[start..]) {
+        let closing = !captures[1].is_empty();
+        let name = captures[2].to_ascii_uppercase();
+        let value = decode_entities(captures[3].trim());
+
+        match (closing, name.as_str()) {
+            (false, "STMTTRN") => current = Some(StatementTransaction::default()),
+            (true, "STMTTRN") => {
+                let txn = current
+                    .take()
+                    .ok_or_else(|| anyhow!("unexpected </STMTTRN>"))?;
+                txns.push(to_transaction(txn, &bank, &account, &currency)?);
+            }
+            // A new account aggregate may have no BANKID
+            (false, "BANKACCTFROM" | "CCACCTFROM") => bank.clear(),
+            (false, "BANKID") => bank = value,
+            (false, "ACCTID") => account = value,
+            (false, "CURDEF") => currency = value,
+            (false, field) if !value.is_empty() => {
+                if let Some(txn) = current.as_mut() {
+                    match field {
+                        "FITID" => txn.fitid = Some(value),
+                        "DTPOSTED" => txn.dtposted = Some(value),
+                        "TRNAMT" => txn.trnamt = Some(value),
+                        "NAME" => txn.name = Some(value),
+                        "MEMO" => txn.memo = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if current.is_some() {
+        bail!("statement ends inside a <STMTTRN>")
+    }
+
+    Ok(txns)
+}
+
+fn to_transaction(
+    txn: StatementTransaction,
+    bank: &str,
+    account: &str,
+    currency: &str,
+) -> Result<Transaction> {
+    let fitid = txn
+        .fitid
+        .ok_or_else(|| anyhow!("transaction has no FITID"))?;
+    let context = || format!("parsing transaction {}", fitid);
+
+    // Dates look like YYYYMMDD[HHMMSS[.XXX]][[gmt offset:tz name]]
+    let dtposted = txn
+        .dtposted
+        .ok_or_else(|| anyhow!("no DTPOSTED"))
+        .with_context(context)?;
+    let date = dtposted
+        .get(..8)
+        .ok_or_else(|| anyhow!("invalid DTPOSTED '{}'", dtposted))
+        .and_then(|d| Ok(NaiveDate::parse_from_str(d, "%Y%m%d")?))
+        .with_context(context)?;
+    let amount = txn
+        .trnamt
+        .ok_or_else(|| anyhow!("no TRNAMT"))
+        .and_then(|a| Ok(Decimal::from_str(&a.replace(',', "."))?))
+        .with_context(context)?;
+
+    let description = match (txn.name, txn.memo) {
+        (Some(name), Some(memo)) if memo != name => format!("{} ({})", name, memo),
+        (Some(name), _) => name,
+        (None, Some(memo)) => memo,
+        (None, None) => String::new(),
+    };
+
+    // IDs are stored in expense details, separated by whitespace
+    let fitid: String = fitid.split_whitespace().collect();
+    Ok(Transaction {
+        id: format!("ofx:{:016x}:{}", fnv1a(&[bank, account]), fitid),
+        date,
+        // OFX amounts are negative for debits
+        amount: -amount,
+        currency: currency.to_string(),
+        account: mask_account(account),
+        description,
+        category: None,
+        category_parent: None,
+    })
+}
+
+/// Account number with all but its last 4 characters hidden, since card
+/// numbers are often used as account IDs.
+fn mask_account(account: &str) -> String {
+    let chars: Vec<char> = account.chars().collect();
+    let hidden = chars.len().saturating_sub(4);
+    "*".repeat(hidden) + &chars[hidden..].iter().collect::<String>()
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sgml_statement_works() {
+        let statement = "\
+OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKACCTFROM><BANKID>123<ACCTID>98765<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20230301<DTEND>20230331
+<STMTTRN>
+<TRNTYPE>DEBIT
+<DTPOSTED>20230302120000.000[-5:EST]
+<TRNAMT>-42.10
+<FITID>2023030201
+<NAME>GROCERY STORE
+<MEMO>POS PURCHASE
+</STMTTRN>
+<STMTTRN>
+<TRNTYPE>CREDIT
+<DTPOSTED>20230305
+<TRNAMT>12.00
+<FITID>2023030501
+<NAME>A &amp; B REFUND
+</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>
+";
+        let txns = parse_ofx_transactions(statement).unwrap();
+        assert_eq!(txns.len(), 2);
+        assert_eq!(
+            txns[0].id,
+            format!("ofx:{:016x}:2023030201", fnv1a(&["123", "98765"]))
+        );
+        assert_eq!(txns[0].date, NaiveDate::from_ymd_opt(2023, 3, 2).unwrap());
+        assert_eq!(txns[0].amount, Decimal::new(4210, 2));
+        assert_eq!(txns[0].currency, "USD");
+        assert_eq!(txns[0].account, "*8765");
+        assert_eq!(txns[0].description, "GROCERY STORE (POS PURCHASE)");
+        assert_eq!(txns[1].amount, Decimal::new(-1200, 2));
+        assert_eq!(txns[1].description, "A & B REFUND");
+    }
+
+    #[test]
+    fn parse_xml_statement_works() {
+        let statement = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CURDEF>CAD</CURDEF>
+    <CCACCTFROM><ACCTID>4111</ACCTID></CCACCTFROM>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>DEBIT</TRNTYPE>
+        <DTPOSTED>20230310</DTPOSTED>
+        <TRNAMT>-9.99</TRNAMT>
+        <FITID>abc-1</FITID>
+        <NAME>STREAMING</NAME>
+      </STMTTRN>
+    </BANKTRANLIST>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>
+"#;
+        let txns = parse_ofx_transactions(statement).unwrap();
+        assert_eq!(txns.len(), 1);
+        assert_eq!(
+            txns[0].id,
+            format!("ofx:{:016x}:abc-1", fnv1a(&["", "4111"]))
+        );
+        assert_eq!(txns[0].amount, Decimal::new(999, 2));
+        assert_eq!(txns[0].account, "4111");
+        assert_eq!(txns[0].currency, "CAD");
+        assert_eq!(txns[0].description, "STREAMING");
+    }
+
+    #[test]
+    fn same_fitid_in_two_accounts_gets_distinct_ids() {
+        let statement = "\
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKACCTFROM><BANKID>123<ACCTID>111<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><DTPOSTED>20230302<TRNAMT>-5.00<FITID>1</STMTTRN>
+</BANKTRANLIST>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+<CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+<CCACCTFROM><ACCTID>222</CCACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><DTPOSTED>20230302<TRNAMT>-5.00<FITID>1</STMTTRN>
+</BANKTRANLIST>
+</CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>
+";
+        let txns = parse_ofx_transactions(statement).unwrap();
+        assert_ne!(txns[0].id, txns[1].id);
+        assert!(txns.iter().all(|t| t.id.ends_with(":1")));
+        assert!(txns
+            .iter()
+            .all(|t| !t.id.contains("111") && !t.id.contains("222")));
+    }
+
+    #[test]
+    fn parse_rejects_transaction_without_fitid() {
+        let statement = "<OFX><STMTTRN><DTPOSTED>20230310<TRNAMT>-1.00</STMTTRN></OFX>";
+        assert!(parse_ofx_transactions(statement).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::Write;
+
+use anyhow::bail;
+use anyhow::Result;
+use clap::Parser;
+use serde::Serialize;
+use splitwise::model::users::User;
+use splitwise::model::Balance;
+
+/// Command line flag choosing between table and JSON output.
+#[derive(Parser)]
+pub(crate) struct OutputArgs {
+    /// Print the full API objects as JSON instead of a table
+    #[clap(long, global = true)]
+    pub json: bool,
+}
+
+impl OutputArgs {
+    /// Prints a value as JSON, or as the table built from it.
+    pub fn print<T: Serialize>(&self, value: &T, table: impl FnOnce(&T) -> Table) -> Result<()> {
+        if self.json {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, value)?;
+            writeln!(stdout)?;
+        } else {
+            table(value).write(&mut std::io::stdout().lock())?;
+        }
+        Ok(())
+    }
+}
+
+/// Plain text table with columns padded to their widest cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Self {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Key and value table, for showing a single object.
+    pub fn fields(fields: &[(&'static str, String)]) -> Self {
+        let mut table = Self::new(&["Field", "Value"]);
+        for (name, value) in fields {
+            table.row(vec![name.to_string(), value.clone()]);
+        }
+        table
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in self.rows.iter() {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        for row in std::iter::once(&headers).chain(self.rows.iter()) {
+            let mut line = String::new();
+            for (i, (cell, width)) in row.iter().zip(widths.iter()).enumerate() {
+                if i > 0 {
+                    line.push_str("  ");
+                }
+                line.push_str(cell);
+                let padding = width - cell.chars().count();
+                line.extend(std::iter::repeat_n(' ', padding));
+            }
+            writeln!(writer, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Full name of a user, falling back to their email or ID.
+pub(crate) fn user_name(user: &User) -> String {
+    let name = [user.first_name.as_deref(), user.last_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !name.is_empty() {
+        return name;
+    }
+    user.email
+        .clone()
+        .unwrap_or_else(|| user.id.unwrap_or_default().to_string())
+}
+
+/// Balances in several currencies, eg `12.50 USD, -3.00 EUR`.
+pub(crate) fn balances(balances: Option<&Vec<Balance>>) -> String {
+    balances
+        .into_iter()
+        .flatten()
+        .map(|b| {
+            format!(
+                "{} {}",
+                b.amount.as_deref().unwrap_or("0.00"),
+                b.currency_code.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Optional value as a table cell.
+pub(crate) fn cell<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Fails with the errors of an API response reporting no success.
+pub(crate) fn check_success(success: bool, errors: Vec<String>, action: &str) -> Result<()> {
+    if success {
+        return Ok(());
+    }
+    if errors.is_empty() {
+        bail!("failed to {}", action)
+    }
+    bail!("failed to {}: {}", action, errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_pads_columns_to_widest_cell() {
+        let mut table = Table::new(&["ID", "Name"]);
+        table.row(vec!["1".to_string(), "Rent".to_string()]);
+        table.row(vec!["1234".to_string(), "Groceries".to_string()]);
+
+        let mut out = Vec::new();
+        table.write(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ID    Name\n1     Rent\n1234  Groceries\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde::Serialize;
+use splitwise::client::Client;
+use splitwise::model::expenses::CreateExpenseRequest;
+use splitwise::model::expenses::ListExpensesRequest;
+use splitwise::model::expenses::UserShare;
+
+use crate::matcher::MatchOptions;
+use crate::matcher::Matcher;
+
+/// Version of the plan format written by `sync --plan`.
+const PLAN_VERSION: u32 = 1;
+
+/// Expenses proposed by `sync --plan`, to be reviewed, edited and then
+/// created by `sync --apply`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SyncPlan {
+    pub version: u32,
+    pub created_at: DateTime<Utc>,
+    /// Group the expenses are created in
+    pub group_id: i64,
+    pub expenses: Vec<PlannedExpense>,
+}
+
+/// Expense proposed for a transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PlannedExpense {
+    /// Stored in the expense details, so the expense is never created twice
+    pub transaction_id: String,
+    /// Set to false to leave the transaction out
+    #[serde(default = "approved_by_default")]
+    pub approved: bool,
+    pub date: NaiveDate,
+    pub description: String,
+    pub cost: Decimal,
+    pub currency: String,
+    pub category_id: i64,
+    /// Shares of each user, or none to split equally
+    #[serde(default)]
+    pub shares: Option<Vec<PlannedShare>>,
+    /// ID of the created expense, once applied
+    #[serde(default)]
+    pub expense_id: Option<i64>,
+    /// Why creating the expense failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a user paid and owes for a planned expense.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct PlannedShare {
+    pub user_id: i64,
+    pub paid_share: Decimal,
+    pub owed_share: Decimal,
+}
+
+fn approved_by_default() -> bool {
+    true
+}
+
+impl SyncPlan {
+    /// Builds a plan from the requests `sync` would send.
+    pub fn new(group_id: i64, requests: &[CreateExpenseRequest]) -> Result<Self> {
+        let expenses = requests
+            .iter()
+            .map(PlannedExpense::try_from)
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: PLAN_VERSION,
+            created_at: Utc::now(),
+            group_id,
+            expenses,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening plan {}", path.display()))?;
+        let plan: Self = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("parsing plan {}", path.display()))?;
+        if plan.version > PLAN_VERSION {
+            bail!(
+                "plan version {} is newer than the supported version {}",
+                plan.version,
+                PLAN_VERSION
+            )
+        }
+        Ok(plan)
+    }
+
+    /// Writes the plan, replacing the file only once it is fully written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Creates the approved expenses that were not created yet, recording
+    /// their IDs. Expenses found in the group with the same transaction ID
+    /// are recorded instead of created again, so applying a plan twice, or
+    /// after an interrupted run, creates each expense once.
+    pub async fn apply(&mut self, client: &Client) -> Result<()> {
+        let existing = client
+            .expenses()
+            .list_all_expenses(ListExpensesRequest {
+                group_id: Some(self.group_id),
+                ..ListExpensesRequest::default()
+            })
+            .await?;
+        let matcher = Matcher::new(&existing, MatchOptions::default());
+
+        let mut pending = Vec::new();
+        for (i, planned) in self.expenses.iter_mut().enumerate() {
+            if !planned.approved || planned.expense_id.is_some() {
+                continue;
+            }
+            match matcher.find_external(&planned.transaction_id) {
+                Some(expense) => planned.expense_id = expense.id,
+                None => pending.push(i),
+            }
+        }
+
+        let requests = pending
+            .iter()
+            .map(|i| self.expenses[*i].request(self.group_id))
+            .collect();
+        let results = client.expenses().create_expenses(requests).await;
+        for (i, result) in pending.into_iter().zip(results) {
+            let planned = &mut self.expenses[i];
+            match result {
+                Ok(created) => {
+                    planned.expense_id = created.first().and_then(|e| e.id);
+                    planned.error = None;
+                }
+                Err(e) => planned.error = Some(e.to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PlannedExpense {
+    fn request(&self, group_id: i64) -> CreateExpenseRequest {
+        let users = self.shares.as_ref().map(|shares| {
+            shares
+                .iter()
+                .map(|s| UserShare {
+                    user_id: Some(s.user_id),
+                    paid_share: Some(format!("{:.2}", s.paid_share)),
+                    owed_share: Some(format!("{:.2}", s.owed_share)),
+                    ..UserShare::default()
+                })
+                .collect()
+        });
+        CreateExpenseRequest {
+            cost: format!("{:.2}", self.cost),
+            description: self.description.clone(),
+            details: Some(self.transaction_id.clone()),
+            date: Utc.from_utc_datetime(&self.date.and_time(NaiveTime::default())),
+            repeat_interval: "never".to_string(),
+            currency_code: self.currency.clone(),
+            category_id: self.category_id,
+            group_id,
+            split_equally: users.is_none(),
+            payment: None,
+            users,
+        }
+    }
+}
+
+impl TryFrom<&CreateExpenseRequest> for PlannedExpense {
+    type Error = anyhow::Error;
+
+    fn try_from(request: &CreateExpenseRequest) -> Result<Self> {
+        let decimal =
+            |s: &Option<String>| -> Result<Decimal> { Ok(s.as_deref().unwrap_or("0").parse()?) };
+        let shares = match request.users.as_ref() {
+            Some(users) => Some(
+                users
+                    .iter()
+                    .map(|u| {
+                        Ok(PlannedShare {
+                            user_id: u.user_id.context("share has no user ID")?,
+                            paid_share: decimal(&u.paid_share)?,
+                            owed_share: decimal(&u.owed_share)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            transaction_id: request.details.clone().unwrap_or_default(),
+            approved: true,
+            date: request.date.date_naive(),
+            description: request.description.clone(),
+            cost: request.cost.parse()?,
+            currency: request.currency_code.clone(),
+            category_id: request.category_id,
+            shares,
+            expense_id: None,
+            error: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planned_expense_round_trips_requests() {
+        let request = CreateExpenseRequest {
+            cost: "10.00".to_string(),
+            description: "Rent".to_string(),
+            details: Some("ofx:1".to_string()),
+            date: Utc.with_ymd_and_hms(2023, 3, 1, 0, 0, 0).unwrap(),
+            repeat_interval: "never".to_string(),
+            currency_code: "USD".to_string(),
+            category_id: 3,
+            group_id: 5,
+            split_equally: false,
+            payment: None,
+            users: Some(vec![
+                UserShare {
+                    user_id: Some(1),
+                    paid_share: Some("10.00".to_string()),
+                    owed_share: Some("6.00".to_string()),
+                    ..UserShare::default()
+                },
+                UserShare {
+                    user_id: Some(2),
+                    paid_share: Some("0.00".to_string()),
+                    owed_share: Some("4.00".to_string()),
+                    ..UserShare::default()
+                },
+            ]),
+        };
+
+        let plan = SyncPlan::new(5, std::slice::from_ref(&request)).unwrap();
+        let json = serde_json::to_string(&plan).unwrap();
+        let plan: SyncPlan = serde_json::from_str(&json).unwrap();
+        assert_eq!(plan.expenses[0].transaction_id, "ofx:1");
+        assert_eq!(plan.expenses[0].request(5), request);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use clap::Parser;
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use splitwise::model::expenses::Expense;
+use splitwise::model::expenses::ListExpensesRequest;
+
+use crate::client::client;
+use crate::matcher::Match;
+use crate::matcher::MatchArgs;
+use crate::matcher::Matcher;
+use crate::sync::build_regex_smartcase;
+use crate::transaction::open_source;
+use crate::transaction::Transaction;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    /// Path to a statement, in any format accepted by `sync`
+    file: PathBuf,
+
+    /// Splitwise group ID to reconcile the statement with
+    group_id: i64,
+
+    /// Column mapping of CSV statements: `chase`, `mint`, or the path to a
+    /// TOML profile
+    #[clap(long)]
+    csv_profile: Option<String>,
+
+    /// Limit to transactions and expenses after this date, inclusive.
+    /// Defaults to the first transaction date.
+    #[clap(long)]
+    after: Option<NaiveDate>,
+
+    /// Limit to transactions and expenses before this date, inclusive.
+    /// Defaults to the last transaction date.
+    #[clap(long)]
+    before: Option<NaiveDate>,
+
+    /// Regex filter for transaction account name
+    #[clap(short, long, default_value = ".*", parse(try_from_str = build_regex_smartcase))]
+    account: Regex,
+
+    /// Path to write the full report to as JSON
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    matching: MatchArgs,
+}
+
+/// Splitwise expense, as shown in reconciliation reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ExpenseSummary {
+    id: Option<i64>,
+    date: Option<NaiveDate>,
+    cost: Option<Decimal>,
+    currency: Option<String>,
+    description: Option<String>,
+}
+
+impl From<&Expense> for ExpenseSummary {
+    fn from(expense: &Expense) -> Self {
+        Self {
+            id: expense.id,
+            date: expense.date.map(|d| d.date_naive()),
+            cost: expense
+                .cost
+                .as_deref()
+                .and_then(|c| Decimal::from_str(c).ok()),
+            currency: expense.currency_code.clone(),
+            description: expense.description.clone(),
+        }
+    }
+}
+
+/// Transaction matched to an expense, with how far apart they are.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Matched {
+    transaction: Transaction,
+    expense: ExpenseSummary,
+    /// Expense cost minus transaction amount
+    amount_difference: Decimal,
+    /// Days from the transaction to the expense
+    date_drift: i64,
+}
+
+/// Three-way diff of a statement and a Splitwise group.
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    matched: Vec<Matched>,
+    only_in_bank: Vec<Transaction>,
+    only_in_splitwise: Vec<ExpenseSummary>,
+    /// Unmatched incomes, which `sync` only records with --all or as refunds
+    unmatched_incomes: Vec<Transaction>,
+}
+
+pub(crate) async fn reconcile(args: Args) -> Result<()> {
+    let source = open_source(&args.file, args.csv_profile.as_deref())?;
+    let mut txns: Vec<Transaction> = source
+        .transactions()?
+        .into_iter()
+        .filter(|txn| args.account.is_match(&txn.account))
+        .filter(|txn| args.after.is_none_or(|after| txn.date >= after))
+        .filter(|txn| args.before.is_none_or(|before| txn.date <= before))
+        .collect();
+    txns.sort_by_key(|txn| txn.date);
+
+    let (first, last) = match (txns.first(), txns.last()) {
+        (Some(first), Some(last)) => (first.date, last.date),
+        _ => {
+            println!("No transactions to reconcile");
+            return Ok(());
+        }
+    };
+    let after = args.after.unwrap_or(first);
+    let before = args.before.unwrap_or(last);
+
+    // Fetch expenses a little around the range, so drifting dates still match
+    let options = args.matching.options();
+    let margin = Duration::days(options.days_tolerance + 1);
+    let midnight = |date: NaiveDate| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+    let expenses: Vec<Expense> = client()?
+        .expenses()
+        .list_all_expenses(ListExpensesRequest {
+            group_id: Some(args.group_id),
+            dated_after: Some(midnight(after - margin)),
+            dated_before: Some(midnight(before + margin)),
+            ..ListExpensesRequest::default()
+        })
+        .await?
+        .into_iter()
+        .filter(|e| e.deleted_at.is_none() && e.payment != Some(true))
+        .collect();
+
+    let report = diff(
+        &txns,
+        &expenses,
+        &Matcher::new(&expenses, options),
+        after,
+        before,
+    );
+    print_report(&report);
+
+    if let Some(path) = args.output {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), &report)?;
+    }
+    Ok(())
+}
+
+/// Pairs each transaction with at most one expense. Expenses dated outside
+/// the range are only reported if they matched a transaction, and unmatched
+/// incomes are listed apart from missing expenses.
+fn diff(
+    txns: &[Transaction],
+    expenses: &[Expense],
+    matcher: &Matcher,
+    after: NaiveDate,
+    before: NaiveDate,
+) -> Report {
+    let mut report = Report::default();
+    let mut used = HashSet::new();
+    for txn in txns {
+        let candidates = match matcher.find(txn) {
+            Match::External(expense) | Match::Fuzzy(expense, _) => vec![expense],
+            Match::Ambiguous(candidates) => candidates.into_iter().map(|(e, _)| e).collect(),
+            Match::None => Vec::new(),
+        };
+        let expense = candidates.into_iter().find(|e| used.insert(e.id));
+        match expense {
+            Some(expense) => {
+                let summary = ExpenseSummary::from(expense);
+                report.matched.push(Matched {
+                    amount_difference: summary.cost.unwrap_or_default() - txn.amount,
+                    date_drift: summary.date.map_or(0, |d| (d - txn.date).num_days()),
+                    transaction: txn.clone(),
+                    expense: summary,
+                });
+            }
+            None if txn.is_expense() => report.only_in_bank.push(txn.clone()),
+            None => report.unmatched_incomes.push(txn.clone()),
+        }
+    }
+
+    report.only_in_splitwise = expenses
+        .iter()
+        .filter(|e| !used.contains(&e.id))
+        .map(ExpenseSummary::from)
+        .filter(|e| e.date.is_some_and(|d| d >= after && d <= before))
+        .collect();
+    report
+}
+
+fn print_report(report: &Report) {
+    let exact = report
+        .matched
+        .iter()
+        .filter(|m| m.amount_difference.is_zero() && m.date_drift == 0)
+        .count();
+    println!(
+        "Matched {} transactions, {} exactly",
+        report.matched.len(),
+        exact
+    );
+    for m in report
+        .matched
+        .iter()
+        .filter(|m| !m.amount_difference.is_zero() || m.date_drift != 0)
+    {
+        println!(
+            "  ~ {} {:>10} {}  ->  expense {} ({:+} amount, {:+} days)",
+            m.transaction.date,
+            m.transaction.amount,
+            m.transaction.description,
+            m.expense.id.unwrap_or_default(),
+            m.amount_difference,
+            m.date_drift
+        );
+    }
+
+    println!("Only in bank: {}", report.only_in_bank.len());
+    for txn in report.only_in_bank.iter() {
+        println!(
+            "  + {} {:>10} [{}] {}",
+            txn.date, txn.amount, txn.account, txn.description
+        );
+    }
+
+    println!("Only in Splitwise: {}", report.only_in_splitwise.len());
+    for expense in report.only_in_splitwise.iter() {
+        println!(
+            "  - {} {:>10} {} (expense {})",
+            expense.date.map(|d| d.to_string()).unwrap_or_default(),
+            expense.cost.unwrap_or_default(),
+            expense.description.as_deref().unwrap_or_default(),
+            expense.id.unwrap_or_default()
+        );
+    }
+
+    if !report.unmatched_incomes.is_empty() {
+        println!("Unmatched incomes: {}", report.unmatched_incomes.len());
+        for txn in report.unmatched_incomes.iter() {
+            println!(
+                "  ? {} {:>10} [{}] {}",
+                txn.date, txn.amount, txn.account, txn.description
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::matcher::MatchOptions;
+
+    fn transaction(id: &str, description: &str, amount: i64, day: u32) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            date: NaiveDate::from_ymd_opt(2023, 3, day).unwrap(),
+            amount: Decimal::new(amount, 2),
+            currency: "USD".to_string(),
+            account: "Checking".to_string(),
+            description: description.to_string(),
+            category: None,
+            category_parent: None,
+        }
+    }
+
+    #[test]
+    fn diff_splits_matched_and_unmatched() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {"id": 1, "date": "2023-03-02T00:00:00Z", "cost": "20.00", "description": "Pharmacy", "details": "csv:a"},
+            {"id": 2, "date": "2023-03-04T00:00:00Z", "cost": "12.50", "description": "Bakery"},
+            {"id": 3, "date": "2023-03-05T00:00:00Z", "cost": "40.00", "description": "Cash for cleaner"}
+        ]))
+        .unwrap();
+        let txns = vec![
+            transaction("csv:a", "PHARMACY", 1999, 1),
+            transaction("csv:b", "BAKERY", 1250, 4),
+            transaction("csv:c", "BOOKSHOP", 900, 6),
+            transaction("csv:d", "SALARY", -250000, 6),
+        ];
+        let matcher = Matcher::new(&expenses, MatchOptions::default());
+        let report = diff(
+            &txns,
+            &expenses,
+            &matcher,
+            NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 3, 6).unwrap(),
+        );
+
+        assert_eq!(report.matched.len(), 2);
+        assert_eq!(report.matched[0].amount_difference, Decimal::new(1, 2));
+        assert_eq!(report.matched[0].date_drift, 1);
+        assert_eq!(report.only_in_bank.len(), 1);
+        assert_eq!(report.only_in_bank[0].id, "csv:c");
+        assert_eq!(report.unmatched_incomes.len(), 1);
+        assert_eq!(report.unmatched_incomes[0].id, "csv:d");
+        assert_eq!(report.only_in_splitwise.len(), 1);
+        assert_eq!(report.only_in_splitwise[0].id, Some(3));
+    }
+}
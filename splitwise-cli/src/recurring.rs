@@ -0,0 +1,96 @@
+use anyhow::Result;
+use clap::Parser;
+use clap::Subcommand;
+use splitwise::model::expenses::Expense;
+use splitwise::model::expenses::RepeatInterval;
+
+use crate::client::client;
+use crate::output::cell;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct Args {
+    #[clap(flatten)]
+    output: OutputArgs,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the recurring expenses of all groups and friends
+    List,
+    /// Change how often an expense repeats
+    SetInterval {
+        /// Splitwise expense ID
+        id: i64,
+
+        /// One of `never`, `weekly`, `fortnightly`, `monthly` or `yearly`
+        interval: RepeatInterval,
+    },
+    /// Remind involved users before the next occurrence of an expense
+    SetReminder {
+        /// Splitwise expense ID
+        id: i64,
+
+        /// Days in advance: -1 to 7, or 14
+        #[clap(allow_hyphen_values = true, required_unless_present = "off")]
+        days: Option<i64>,
+
+        /// Turn reminders off
+        #[clap(long, conflicts_with = "days")]
+        off: bool,
+    },
+    /// Stop an expense from repeating
+    Stop {
+        /// Splitwise expense ID
+        id: i64,
+    },
+}
+
+pub(crate) async fn recurring(args: Args) -> Result<()> {
+    let client = client()?;
+    let expenses = client.expenses();
+    let output = args.output;
+
+    let list = match args.command {
+        Command::List => expenses.list_recurring_expenses().await?,
+        Command::SetInterval { id, interval } => expenses.set_repeat_interval(id, interval).await?,
+        Command::SetReminder { id, days, .. } => expenses.set_email_reminder(id, days).await?,
+        Command::Stop { id } => expenses.stop_recurrence(id).await?,
+    };
+    output.print(&list, |e| recurring_table(e))
+}
+
+fn recurring_table(expenses: &[Expense]) -> Table {
+    let mut table = Table::new(&[
+        "ID",
+        "Description",
+        "Cost",
+        "Currency",
+        "Group",
+        "Repeats",
+        "Next",
+        "Reminder",
+    ]);
+    for expense in expenses {
+        let reminder = match (expense.email_reminder, expense.email_reminder_in_advance) {
+            (Some(true), Some(days)) => format!("{} days before", days),
+            (Some(true), None) => "yes".to_string(),
+            _ => String::new(),
+        };
+        table.row(vec![
+            cell(expense.id),
+            cell(expense.description.as_ref()),
+            cell(expense.cost.as_ref()),
+            cell(expense.currency_code.as_ref()),
+            cell(expense.group_id),
+            cell(expense.repeat_interval.as_ref()),
+            cell(expense.next_repeat.map(|d| d.date_naive())),
+            reminder,
+        ]);
+    }
+    table
+}
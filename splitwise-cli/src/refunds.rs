@@ -0,0 +1,230 @@
+use std::str::FromStr;
+
+use chrono::Duration;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use splitwise::model::expenses::CreateExpenseRequest;
+use splitwise::model::expenses::Expense;
+use splitwise::model::expenses::UpdateExpenseRequest;
+use splitwise::model::expenses::UserShare;
+
+use crate::matcher::similarity;
+use crate::transaction::Transaction;
+
+/// How long after a purchase a refund of it is looked for.
+const REFUND_WINDOW_DAYS: i64 = 90;
+
+/// Minimum description similarity between a refund and its purchase.
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// Finds the expense a refund transaction most likely refunds: a purchase
+/// from the same merchant, dated before the refund, costing at least as much.
+pub(crate) fn find_original<'e>(txn: &Transaction, expenses: &'e [Expense]) -> Option<&'e Expense> {
+    let refund = -txn.amount;
+    if refund <= Decimal::ZERO {
+        return None;
+    }
+    let earliest = txn.date - Duration::days(REFUND_WINDOW_DAYS);
+
+    expenses
+        .iter()
+        .filter(|e| e.deleted_at.is_none() && e.payment != Some(true))
+        .filter_map(|e| {
+            let date = e.date?.date_naive();
+            let cost = Decimal::from_str(e.cost.as_deref()?).ok()?;
+            if date < earliest || date > txn.date || cost < refund {
+                return None;
+            }
+            let score = similarity(&txn.description, e.description.as_deref()?);
+            Some((e, score, date))
+        })
+        .filter(|(_, score, _)| *score >= MIN_SIMILARITY)
+        // Most similar first, then most recent
+        .max_by(|a, b| a.1.total_cmp(&b.1).then(a.2.cmp(&b.2)))
+        .map(|(e, _, _)| e)
+}
+
+/// Builds an expense offsetting part of the original one: each user gets
+/// back what they paid and gives back what they owed, in proportion.
+pub(crate) fn offsetting_expense(
+    txn: &Transaction,
+    original: &Expense,
+    group_id: i64,
+) -> Option<CreateExpenseRequest> {
+    let refund = -txn.amount;
+    Some(CreateExpenseRequest {
+        cost: format!("{:.2}", refund),
+        description: format!(
+            "Refund: {}",
+            original.description.as_deref().unwrap_or_default()
+        ),
+        details: Some(txn.id.clone()),
+        date: Utc.from_utc_datetime(&txn.date.and_time(NaiveTime::default())),
+        repeat_interval: "never".to_string(),
+        currency_code: original
+            .currency_code
+            .clone()
+            .unwrap_or_else(|| txn.currency.clone()),
+        category_id: original.category_id.unwrap_or(0),
+        group_id: original.group_id.unwrap_or(group_id),
+        split_equally: false,
+        payment: None,
+        users: Some(scaled_shares(original, refund, true)?),
+    })
+}
+
+/// Builds the update reducing the original expense by the refund, keeping
+/// everyone's share in proportion. Returns `None` if the refund covers the
+/// whole expense.
+pub(crate) fn reduced_original(
+    txn: &Transaction,
+    original: &Expense,
+) -> Option<UpdateExpenseRequest> {
+    let cost = Decimal::from_str(original.cost.as_deref()?).ok()?;
+    let reduced = cost + txn.amount;
+    if reduced <= Decimal::ZERO {
+        return None;
+    }
+
+    // Keep the refund ID in the details so the refund is not synced again
+    let details = match original.details.as_deref() {
+        Some(details) if !details.is_empty() => format!("{}\n{}", details, txn.id),
+        _ => txn.id.clone(),
+    };
+    Some(UpdateExpenseRequest {
+        cost: Some(format!("{:.2}", reduced)),
+        details: Some(details),
+        users: Some(scaled_shares(original, reduced, false)?),
+        ..UpdateExpenseRequest::default()
+    })
+}
+
+/// Scales the shares of an expense to a new cost, swapping paid and owed
+/// shares if `reverse` is set. Rounding leftovers go to the first share, so
+/// that both paid and owed shares add up to the new cost.
+pub(crate) fn scaled_shares(
+    expense: &Expense,
+    new_cost: Decimal,
+    reverse: bool,
+) -> Option<Vec<UserShare>> {
+    let cost = Decimal::from_str(expense.cost.as_deref()?).ok()?;
+    if cost.is_zero() {
+        return None;
+    }
+    let parse = |share: &Option<String>| {
+        share
+            .as_deref()
+            .and_then(|s| Decimal::from_str(s).ok())
+            .unwrap_or_default()
+    };
+
+    let mut shares: Vec<(i64, Decimal, Decimal)> = Vec::new();
+    for share in expense.users.iter().flatten() {
+        let user_id = share.user_id()?;
+        let (paid, owed) = (parse(&share.paid_share), parse(&share.owed_share));
+        let (paid, owed) = if reverse { (owed, paid) } else { (paid, owed) };
+        shares.push((
+            user_id,
+            (paid * new_cost / cost).round_dp(2),
+            (owed * new_cost / cost).round_dp(2),
+        ));
+    }
+    let paid_total: Decimal = shares.iter().map(|s| s.1).sum();
+    let owed_total: Decimal = shares.iter().map(|s| s.2).sum();
+    let first = shares.first_mut()?;
+    first.1 += new_cost - paid_total;
+    first.2 += new_cost - owed_total;
+
+    Some(
+        shares
+            .into_iter()
+            .map(|(user_id, paid, owed)| UserShare {
+                user_id: Some(user_id),
+                paid_share: Some(format!("{:.2}", paid)),
+                owed_share: Some(format!("{:.2}", owed)),
+                ..UserShare::default()
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::*;
+
+    fn refund(description: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: "ofx:7".to_string(),
+            date: NaiveDate::from_ymd_opt(2023, 3, 10).unwrap(),
+            amount: Decimal::new(-amount, 2),
+            currency: "USD".to_string(),
+            account: "Chase".to_string(),
+            description: description.to_string(),
+            category: None,
+            category_parent: None,
+        }
+    }
+
+    fn expenses() -> Vec<Expense> {
+        serde_json::from_value(json!([
+            {"id": 1, "group_id": 5, "date": "2023-03-01T00:00:00Z", "cost": "30.00", "description": "HARDWARE STORE",
+             "users": [
+                {"user_id": 1, "paid_share": "30.00", "owed_share": "15.00"},
+                {"user_id": 2, "paid_share": "0.00", "owed_share": "15.00"}
+             ]},
+            {"id": 2, "group_id": 5, "date": "2023-03-02T00:00:00Z", "cost": "80.00", "description": "Groceries"}
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn find_original_matches_merchant_and_cost() {
+        let expenses = expenses();
+        let original = find_original(&refund("HARDWARE STORE #12", 1000), &expenses);
+        assert_eq!(original.and_then(|e| e.id), Some(1));
+        assert!(find_original(&refund("HARDWARE STORE #12", 5000), &expenses).is_none());
+        assert!(find_original(&refund("AIRLINE", 1000), &expenses).is_none());
+    }
+
+    #[test]
+    fn refund_shares_are_reversed_or_scaled() {
+        let expenses = expenses();
+        let txn = refund("HARDWARE STORE", 1000);
+
+        let offset = offsetting_expense(&txn, &expenses[0], 5).unwrap();
+        let shares: Vec<(&str, &str)> = offset
+            .users
+            .iter()
+            .flatten()
+            .map(|s| {
+                (
+                    s.paid_share.as_deref().unwrap(),
+                    s.owed_share.as_deref().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(offset.cost, "10.00");
+        assert_eq!(shares, vec![("5.00", "10.00"), ("5.00", "0.00")]);
+
+        let reduced = reduced_original(&txn, &expenses[0]).unwrap();
+        let shares: Vec<(&str, &str)> = reduced
+            .users
+            .iter()
+            .flatten()
+            .map(|s| {
+                (
+                    s.paid_share.as_deref().unwrap(),
+                    s.owed_share.as_deref().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(reduced.cost.as_deref(), Some("20.00"));
+        assert_eq!(reduced.details.as_deref(), Some("ofx:7"));
+        assert_eq!(shares, vec![("20.00", "10.00"), ("0.00", "10.00")]);
+    }
+}
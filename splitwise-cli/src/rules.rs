@@ -0,0 +1,325 @@
+use std::path::Path;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use splitwise::model::expenses::UserShare;
+
+use crate::transaction::Transaction;
+
+/// Rules deciding how transactions are recorded in Splitwise, read from a
+/// TOML file of `[[rule]]` tables. The first rule matching a transaction
+/// applies, eg:
+///
+/// ```toml
+/// [[rule]]
+/// description = "(?i)landlord"
+/// min_amount = 1000
+/// rename = "Rent"
+/// category_id = 3
+/// payer = 111
+/// split = [{ user_id = 111, weight = 60 }, { user_id = 222, weight = 40 }]
+/// ```
+#[derive(Debug, Default)]
+pub(crate) struct Rules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    account: Option<String>,
+    description: Option<String>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    source_category: Option<String>,
+    rename: Option<String>,
+    category_id: Option<i64>,
+    payer: Option<i64>,
+    split: Option<Vec<Weight>>,
+}
+
+/// Part of an expense owed by a user, relative to the other weights.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Weight {
+    pub user_id: i64,
+    pub weight: Decimal,
+}
+
+#[derive(Debug)]
+struct Rule {
+    account: Option<Regex>,
+    description: Option<Regex>,
+    min_amount: Option<Decimal>,
+    max_amount: Option<Decimal>,
+    source_category: Option<String>,
+    rename: Option<String>,
+    category_id: Option<i64>,
+    payer: Option<i64>,
+    split: Option<Vec<Weight>>,
+}
+
+/// What the rule matching a transaction decided.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Outcome {
+    /// Expense description, if rewritten.
+    pub description: Option<String>,
+    pub category_id: Option<i64>,
+    /// User paying the whole expense.
+    pub payer: Option<i64>,
+    /// How the expense is split, if not equally.
+    pub split: Option<Vec<Weight>>,
+}
+
+impl Rules {
+    /// Reads rules from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading rules {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("parsing rules {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let file: RulesFile = toml::from_str(contents)?;
+        let regex = |pattern: Option<String>| pattern.map(|p| Regex::new(&p)).transpose();
+        let mut rules = Vec::new();
+        for (i, config) in file.rule.into_iter().enumerate() {
+            if let Some(split) = config.split.as_ref() {
+                if split.is_empty() || split.iter().any(|w| w.weight.is_sign_negative()) {
+                    bail!("rule {} needs a split with non-negative weights", i + 1)
+                }
+                if split.iter().all(|w| w.weight.is_zero()) {
+                    bail!("rule {} has a split with only zero weights", i + 1)
+                }
+            }
+            rules.push(Rule {
+                account: regex(config.account)?,
+                description: regex(config.description)?,
+                min_amount: config.min_amount,
+                max_amount: config.max_amount,
+                source_category: config.source_category,
+                rename: config.rename,
+                category_id: config.category_id,
+                payer: config.payer,
+                split: config.split,
+            });
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the first rule matching the transaction.
+    pub fn apply(&self, txn: &Transaction) -> Outcome {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.apply(txn))
+            .unwrap_or_default()
+    }
+}
+
+impl Rule {
+    fn apply(&self, txn: &Transaction) -> Option<Outcome> {
+        if let Some(account) = self.account.as_ref() {
+            if !account.is_match(&txn.account) {
+                return None;
+            }
+        }
+        if self.min_amount.is_some_and(|min| txn.amount < min)
+            || self.max_amount.is_some_and(|max| txn.amount > max)
+        {
+            return None;
+        }
+        if let Some(category) = self.source_category.as_ref() {
+            if !txn
+                .category
+                .as_ref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(category))
+            {
+                return None;
+            }
+        }
+        let captures = match self.description.as_ref() {
+            Some(description) => Some(description.captures(&txn.description)?),
+            None => None,
+        };
+
+        // Renames may refer to groups of the description pattern, eg `$1`
+        let description = self.rename.as_ref().map(|rename| match captures {
+            Some(captures) => {
+                let mut expanded = String::new();
+                captures.expand(rename, &mut expanded);
+                expanded
+            }
+            None => rename.clone(),
+        });
+        Some(Outcome {
+            description,
+            category_id: self.category_id,
+            payer: self.payer,
+            split: self.split.clone(),
+        })
+    }
+}
+
+impl Outcome {
+    /// Shares of an expense with the given cost, paid by the rule's payer or
+    /// else `default_payer`. Without a split, the cost is split equally among
+    /// `members`. Returns `None` when the expense is simply paid by the
+    /// current user and split equally.
+    pub fn shares(
+        &self,
+        cost: Decimal,
+        default_payer: i64,
+        members: &[i64],
+    ) -> Option<Vec<UserShare>> {
+        if self.payer.is_none() && self.split.is_none() {
+            return None;
+        }
+        let payer = self.payer.unwrap_or(default_payer);
+        let weights = match self.split.as_ref() {
+            Some(split) => split.clone(),
+            None => members
+                .iter()
+                .map(|user_id| Weight {
+                    user_id: *user_id,
+                    weight: Decimal::ONE,
+                })
+                .collect(),
+        };
+
+        let total: Decimal = weights.iter().map(|w| w.weight).sum();
+        let mut owed: Vec<(i64, Decimal)> = weights
+            .iter()
+            .map(|w| (w.user_id, (cost * w.weight / total).round_dp(2)))
+            .collect();
+        // Rounding leftovers go to the first user, so shares add up to the cost
+        let remainder = cost - owed.iter().map(|(_, o)| *o).sum::<Decimal>();
+        if let Some((_, first)) = owed.first_mut() {
+            *first += remainder;
+        }
+        if !owed.iter().any(|(user_id, _)| *user_id == payer) {
+            owed.push((payer, Decimal::ZERO));
+        }
+
+        Some(
+            owed.into_iter()
+                .map(|(user_id, owed)| UserShare {
+                    user_id: Some(user_id),
+                    paid_share: Some(format!(
+                        "{:.2}",
+                        if user_id == payer {
+                            cost
+                        } else {
+                            Decimal::ZERO
+                        }
+                    )),
+                    owed_share: Some(format!("{:.2}", owed)),
+                    ..UserShare::default()
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn transaction(description: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: "csv:1".to_string(),
+            date: NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            amount: Decimal::new(amount, 2),
+            currency: "USD".to_string(),
+            account: "Checking".to_string(),
+            description: description.to_string(),
+            category: Some("Groceries".to_string()),
+            category_parent: None,
+        }
+    }
+
+    #[test]
+    fn apply_uses_first_matching_rule() {
+        let rules = Rules::parse(
+            r#"
+            [[rule]]
+            description = "LANDLORD (\\w+)"
+            min_amount = 1000
+            rename = "Rent $1"
+            split = [{ user_id = 1, weight = 60 }, { user_id = 2, weight = 40 }]
+
+            [[rule]]
+            source_category = "groceries"
+            category_id = 12
+            "#,
+        )
+        .unwrap();
+
+        let rent = rules.apply(&transaction("LANDLORD MARCH", 150000));
+        assert_eq!(rent.description.as_deref(), Some("Rent MARCH"));
+        assert_eq!(rent.category_id, None);
+
+        let small = rules.apply(&transaction("LANDLORD FEE", 2000));
+        assert_eq!(small.category_id, Some(12));
+        assert_eq!(small.split, None);
+    }
+
+    #[test]
+    fn shares_follow_weights_and_add_up() {
+        let outcome = Outcome {
+            payer: Some(3),
+            split: Some(vec![
+                Weight {
+                    user_id: 1,
+                    weight: Decimal::ONE,
+                },
+                Weight {
+                    user_id: 2,
+                    weight: Decimal::TWO,
+                },
+            ]),
+            ..Outcome::default()
+        };
+
+        let shares = outcome.shares(Decimal::new(1000, 2), 1, &[]).unwrap();
+        let owed: Vec<&str> = shares
+            .iter()
+            .map(|s| s.owed_share.as_deref().unwrap())
+            .collect();
+        let paid: Vec<&str> = shares
+            .iter()
+            .map(|s| s.paid_share.as_deref().unwrap())
+            .collect();
+        assert_eq!(owed, vec!["3.33", "6.67", "0.00"]);
+        assert_eq!(paid, vec!["0.00", "0.00", "10.00"]);
+
+        let payer_only = Outcome {
+            payer: Some(2),
+            ..Outcome::default()
+        };
+        let shares = payer_only
+            .shares(Decimal::new(1000, 2), 1, &[1, 2, 3])
+            .unwrap();
+        let owed: Vec<&str> = shares
+            .iter()
+            .map(|s| s.owed_share.as_deref().unwrap())
+            .collect();
+        assert_eq!(owed, vec!["3.34", "3.33", "3.33"]);
+        assert_eq!(Outcome::default().shares(Decimal::ONE, 1, &[1, 2]), None);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::transaction::Transaction;
+use crate::transaction::TransactionSource;
+
+/// How the sign of a statement amount relates to money spent.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum AmountSign {
+    /// Purchases are negative, refunds and payments positive (eg Chase).
+    DebitNegative,
+    /// Purchases are positive, refunds and payments negative.
+    DebitPositive,
+    /// Amounts are always positive, and `type_column` says whether a row is a
+    /// debit (eg Mint CSV exports).
+    TypeColumn,
+}
+
+/// Maps the columns of a bank's CSV statement to transaction fields.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(crate) struct CsvProfile {
+    pub date_column: String,
+    /// `strftime` format of the date column
+    pub date_format: String,
+    pub amount_column: String,
+    pub amount_sign: AmountSign,
+    /// Column holding the debit/credit type, for `amount-sign = "type-column"`
+    pub type_column: Option<String>,
+    /// Value of `type_column` for debits, compared case insensitively
+    #[serde(default = "default_debit_value")]
+    pub debit_value: String,
+    pub description_column: String,
+    pub category_column: Option<String>,
+    /// Column appended to the description, if not empty
+    pub memo_column: Option<String>,
+    pub account_column: Option<String>,
+    /// Account name for statements without an account column
+    pub account: Option<String>,
+    /// ISO 4217 currency code of the amounts
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+fn default_debit_value() -> String {
+    "debit".to_string()
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+impl CsvProfile {
+    /// Profile for Chase credit card statements.
+    pub fn chase() -> Self {
+        Self {
+            date_column: "Transaction Date".to_string(),
+            date_format: "%m/%d/%Y".to_string(),
+            amount_column: "Amount".to_string(),
+            amount_sign: AmountSign::DebitNegative,
+            type_column: None,
+            debit_value: default_debit_value(),
+            description_column: "Description".to_string(),
+            category_column: Some("Category".to_string()),
+            memo_column: Some("Memo".to_string()),
+            account_column: None,
+            account: Some("Chase".to_string()),
+            currency: default_currency(),
+        }
+    }
+
+    /// Profile for Mint transaction CSV exports.
+    pub fn mint() -> Self {
+        Self {
+            date_column: "Date".to_string(),
+            date_format: "%m/%d/%Y".to_string(),
+            amount_column: "Amount".to_string(),
+            amount_sign: AmountSign::TypeColumn,
+            type_column: Some("Transaction Type".to_string()),
+            debit_value: default_debit_value(),
+            description_column: "Description".to_string(),
+            category_column: Some("Category".to_string()),
+            memo_column: Some("Notes".to_string()),
+            account_column: Some("Account Name".to_string()),
+            account: None,
+            currency: default_currency(),
+        }
+    }
+
+    /// Returns the built-in profile with the given name, or else reads a
+    /// profile from the TOML file at that path.
+    pub fn load(name_or_path: &str) -> Result<Self> {
+        match name_or_path {
+            "chase" => Ok(Self::chase()),
+            "mint" => Ok(Self::mint()),
+            path => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("reading CSV profile {}", path))?;
+                let profile: Self = toml::from_str(&contents)
+                    .with_context(|| format!("parsing CSV profile {}", path))?;
+                if profile.amount_sign == AmountSign::TypeColumn && profile.type_column.is_none() {
+                    bail!("CSV profile {} needs a type_column", path)
+                }
+                Ok(profile)
+            }
+        }
+    }
+}
+
+/// Transactions from a CSV statement file.
+pub(crate) struct CsvSource {
+    path: PathBuf,
+    profile: CsvProfile,
+}
+
+impl CsvSource {
+    pub fn new(path: PathBuf, profile: CsvProfile) -> Self {
+        Self { path, profile }
+    }
+}
+
+impl TransactionSource for CsvSource {
+    fn transactions(&self) -> Result<Vec<Transaction>> {
+        parse_csv_transactions(std::fs::File::open(&self.path)?, &self.profile)
+    }
+}
+
+/// Parses a CSV statement. CSV rows have no stable ID, so each transaction is
+/// identified by a hash of its fields, numbered to tell identical rows apart.
+pub(crate) fn parse_csv_transactions<R: Read>(
+    reader: R,
+    profile: &CsvProfile,
+) -> Result<Vec<Transaction>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim() == name)
+            .ok_or_else(|| anyhow!("CSV statement has no column '{}'", name))
+    };
+    let optional_column = |name: &Option<String>| name.as_deref().map(column).transpose();
+
+    let date_column = column(&profile.date_column)?;
+    let amount_column = column(&profile.amount_column)?;
+    let description_column = column(&profile.description_column)?;
+    let type_column = optional_column(&profile.type_column)?;
+    let category_column = optional_column(&profile.category_column)?;
+    let memo_column = optional_column(&profile.memo_column)?;
+    let account_column = optional_column(&profile.account_column)?;
+
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    let mut txns = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |column: usize| record.get(column).unwrap_or_default().trim();
+        let row = i + 2; // 1-based, after the header
+
+        let date = NaiveDate::parse_from_str(field(date_column), &profile.date_format)
+            .with_context(|| format!("parsing date on row {}", row))?;
+        let amount = parse_amount(field(amount_column))
+            .with_context(|| format!("parsing amount on row {}", row))?;
+        let amount = match profile.amount_sign {
+            AmountSign::DebitNegative => -amount,
+            AmountSign::DebitPositive => amount,
+            AmountSign::TypeColumn => {
+                let debit = type_column
+                    .is_some_and(|c| field(c).eq_ignore_ascii_case(&profile.debit_value));
+                if debit {
+                    amount.abs()
+                } else {
+                    -amount.abs()
+                }
+            }
+        };
+        let mut description = field(description_column).to_string();
+        if let Some(memo) = memo_column.map(field).filter(|m| !m.is_empty()) {
+            description = format!("{} ({})", description, memo);
+        }
+        let account = account_column
+            .map(|c| field(c).to_string())
+            .or_else(|| profile.account.clone())
+            .unwrap_or_default();
+        let category = category_column
+            .map(|c| field(c).to_string())
+            .filter(|c| !c.is_empty());
+
+        let hash = fnv1a(&[
+            &date.to_string(),
+            &format!("{:.2}", amount),
+            &description,
+            &account,
+        ]);
+        let occurrence = seen.entry(hash).or_insert(0);
+        let id = match *occurrence {
+            0 => format!("csv:{:016x}", hash),
+            n => format!("csv:{:016x}-{}", hash, n),
+        };
+        *occurrence += 1;
+
+        txns.push(Transaction {
+            id,
+            date,
+            amount,
+            currency: profile.currency.clone(),
+            account,
+            description,
+            category,
+            category_parent: None,
+        });
+    }
+    Ok(txns)
+}
+
+/// Parses amounts like `-1,234.50`, `$12.00` or `(12.00)`.
+fn parse_amount(s: &str) -> Result<Decimal> {
+    let negative = s.starts_with('(') && s.ends_with(')');
+    let cleaned: String = s
+        .chars()
+        .filter(|c| !matches!(c, '$' | ',' | '(' | ')' | ' '))
+        .collect();
+    let amount = Decimal::from_str(&cleaned)?;
+    Ok(if negative { -amount } else { amount })
+}
+
+/// Hash of the fields, each followed by a NUL byte, so that their
+/// boundaries count.
+pub(crate) fn fnv1a(fields: &[&str]) -> u64 {
+    let bytes: Vec<u8> = fields
+        .iter()
+        .flat_map(|field| field.bytes().chain(std::iter::once(0)))
+        .collect();
+    splitwise::client::fnv1a(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_chase_csv_works() {
+        let statement = "\
+Transaction Date,Post Date,Description,Category,Type,Amount,Memo
+03/01/2023,03/02/2023,GROCERY STORE,Groceries,Sale,-42.10,
+03/01/2023,03/02/2023,GROCERY STORE,Groceries,Sale,-42.10,
+03/03/2023,03/04/2023,GROCERY STORE,Groceries,Return,12.00,damaged
+";
+        let txns = parse_csv_transactions(statement.as_bytes(), &CsvProfile::chase()).unwrap();
+        assert_eq!(txns.len(), 3);
+        assert_eq!(txns[0].amount, Decimal::new(4210, 2));
+        assert_eq!(txns[0].account, "Chase");
+        assert_eq!(txns[1].id, format!("{}-1", txns[0].id));
+        assert_eq!(txns[2].amount, Decimal::new(-1200, 2));
+        assert_eq!(txns[2].description, "GROCERY STORE (damaged)");
+    }
+
+    #[test]
+    fn parse_mint_csv_works() {
+        let statement = "\
+\"Date\",\"Description\",\"Original Description\",\"Amount\",\"Transaction Type\",\"Category\",\"Account Name\",\"Labels\",\"Notes\"
+\"3/01/2023\",\"Coffee\",\"COFFEE SHOP\",\"4.50\",\"debit\",\"Coffee Shops\",\"Checking\",\"\",\"\"
+\"3/02/2023\",\"Paycheck\",\"ACME PAYROLL\",\"1,000.00\",\"credit\",\"Paycheck\",\"Checking\",\"\",\"\"
+";
+        let txns = parse_csv_transactions(statement.as_bytes(), &CsvProfile::mint()).unwrap();
+        assert_eq!(txns[0].amount, Decimal::new(450, 2));
+        assert!(txns[0].is_expense());
+        assert_eq!(txns[0].category.as_deref(), Some("Coffee Shops"));
+        assert_eq!(txns[1].amount, Decimal::new(-100000, 2));
+        assert_eq!(txns[1].account, "Checking");
+    }
+}
@@ -1,8 +1,8 @@
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::bail;
 use anyhow::Result;
 use chrono::NaiveDate;
 use chrono::NaiveTime;
@@ -10,21 +10,63 @@ use chrono::TimeZone;
 use chrono::Utc;
 use clap::Parser;
 use dialoguer::Confirm;
+use dialoguer::Select;
 use regex::Regex;
 use regex::RegexBuilder;
+use splitwise::client::Client;
+use splitwise::client::RateLimit;
 use splitwise::model::expenses::CreateExpenseRequest;
 use splitwise::model::expenses::Expense;
 use splitwise::model::expenses::ListExpensesRequest;
 
-use crate::mint::MintTransaction;
+use crate::categories::CategoryMap;
+use crate::client::client;
+use crate::matcher::Match;
+use crate::matcher::MatchArgs;
+use crate::matcher::Matcher;
+use crate::plan::SyncPlan;
+use crate::refunds::find_original;
+use crate::refunds::offsetting_expense;
+use crate::refunds::reduced_original;
+use crate::rules::Rules;
+use crate::transaction::open_source;
+use crate::transaction::Transaction;
 
 #[derive(Parser)]
 pub(crate) struct Args {
-    /// Path to file containing transactions to process
-    file: PathBuf,
+    /// Path to file containing transactions to process, either Mint JSON
+    /// (`.json`), a CSV statement (`.csv`) or an OFX statement (`.ofx`, `.qfx`)
+    #[clap(required_unless_present = "apply")]
+    file: Option<PathBuf>,
 
     /// Splitwise group ID to sync transactions with
-    group_id: i64,
+    #[clap(required_unless_present = "apply")]
+    group_id: Option<i64>,
+
+    /// Write the proposed expenses to this plan file instead of creating
+    /// them, without prompting
+    #[clap(long, conflicts_with = "apply")]
+    plan: Option<PathBuf>,
+
+    /// Create the approved expenses of a plan file written by --plan, and
+    /// record their IDs in it
+    #[clap(long)]
+    apply: Option<PathBuf>,
+
+    /// Column mapping of CSV statements: `chase`, `mint`, or the path to a
+    /// TOML profile
+    #[clap(long)]
+    csv_profile: Option<String>,
+
+    /// Path to a TOML file of rules setting the category, split, payer or
+    /// description of matching transactions
+    #[clap(long)]
+    rules: Option<PathBuf>,
+
+    /// Path to a TOML file mapping bank categories to Splitwise category
+    /// names or IDs, overriding the built-in mapping
+    #[clap(long)]
+    category_map: Option<PathBuf>,
 
     /// Limit to transactions after this date, inclusive
     #[clap(long)]
@@ -42,11 +84,12 @@ pub(crate) struct Args {
     #[clap(short, long, default_value = ".*", parse(try_from_str = build_regex_smartcase))]
     description: Regex,
 
-    /// Show incomes in addition to expenses
+    /// Show incomes in addition to expenses and refunds
     #[clap(long)]
     all: bool,
 
-    /// Only process this many transactions
+    /// Only process this many transactions, counting expenses, refunds and,
+    /// with --all, other incomes
     #[clap(long)]
     limit: Option<usize>,
 
@@ -57,20 +100,44 @@ pub(crate) struct Args {
     /// Don't write any data back to Splitwise
     #[clap(long)]
     dry_run: bool,
+
+    /// Maximum number of requests per second sent to Splitwise
+    #[clap(long, default_value = "2", parse(try_from_str = parse_rate))]
+    rate: f64,
+
+    #[clap(flatten)]
+    matching: MatchArgs,
 }
 
 pub(crate) async fn sync(args: Args) -> Result<()> {
-    let txns = read_mint_transactions_from_file(args.file)?;
-    let limit = args.limit.unwrap_or_else(|| txns.len());
-    let after_date = args.after.unwrap_or(NaiveDate::MIN);
-    let before_date = args.before.unwrap_or(NaiveDate::MAX);
+    let mut client = client()?.with_rate_limit(RateLimit {
+        requests_per_second: args.rate,
+        ..RateLimit::default()
+    });
+    if args.dry_run {
+        client = client.with_dry_run();
+    }
+
+    if let Some(path) = args.apply.as_deref() {
+        apply_plan(&client, path).await?;
+        print_dry_run(&client);
+        return Ok(());
+    }
+    let (file, group_id) = match (args.file.as_deref(), args.group_id) {
+        (Some(file), Some(group_id)) => (file, group_id),
+        _ => bail!("a statement file and group ID are needed unless applying a plan"),
+    };
+    // Plans are written for later review, so nothing is asked
+    let interactive = !args.assume_yes && args.plan.is_none();
+
+    let source = open_source(file, args.csv_profile.as_deref())?;
+    let txns = filter_transactions(source.transactions()?, &args);
 
     // Fetch existing expenses from Splitwise
-    let client = splitwise::client::Client::default();
     let expenses = client
         .expenses()
         .list_expenses(ListExpensesRequest {
-            group_id: Some(args.group_id),
+            group_id: Some(group_id),
             limit: Some(10000),
             ..Default::default()
         })
@@ -78,62 +145,353 @@ pub(crate) async fn sync(args: Args) -> Result<()> {
     println!(
         "Found {} expenses in Splitwise group {}",
         expenses.len(),
-        args.group_id
+        group_id
     );
 
-    // Filter Mint transactions and sync them to Splitwise
-    for t in txns
+    // The limit counts the transactions that may be synced: expenses, refunds
+    // of synced expenses, and other incomes with --all
+    let txns: Vec<Transaction> = txns
+        .into_iter()
+        .filter(|txn| txn.is_expense() || args.all || find_original(txn, &expenses).is_some())
+        .take(args.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    let mut recorder = Recorder::new(&client, &args, group_id).await?;
+
+    // Collect the new transactions to sync to Splitwise. Transactions that
+    // could match several expenses are left for the user to check.
+    let matcher = Matcher::new(&expenses, args.matching.options());
+    let mut ambiguous = Vec::new();
+    let mut new_txns = Vec::new();
+    for txn in txns.iter() {
+        match matcher.find(txn) {
+            Match::None => new_txns.push(txn),
+            Match::Ambiguous(candidates) => ambiguous.push((txn, candidates)),
+            Match::External(_) | Match::Fuzzy(..) => {}
+        }
+    }
+
+    // Money coming back for a synced purchase is recorded against it, other
+    // incomes are only synced with --all
+    let mut refunds = Vec::new();
+    new_txns.retain(|txn| {
+        if txn.is_expense() {
+            return true;
+        }
+        match find_original(txn, &expenses) {
+            Some(original) => {
+                refunds.push((*txn, original));
+                false
+            }
+            None => args.all,
+        }
+    });
+
+    let mut requests: Vec<CreateExpenseRequest> = new_txns
+        .into_iter()
+        .filter(|txn| !interactive || confirm(txn))
+        .map(|txn| recorder.expense_request(txn))
+        .collect();
+    let mut updates = Vec::new();
+    let mut unrecorded_refunds = Vec::new();
+    for (txn, original) in refunds {
+        let offset = offsetting_expense(txn, original, group_id);
+        let reduced = reduced_original(txn, original);
+        let action = if interactive {
+            choose_refund_action(txn, original, offset.is_some(), reduced.is_some())
+        } else if offset.is_some() {
+            RefundAction::Offset
+        } else {
+            unrecorded_refunds.push((txn, original));
+            RefundAction::Skip
+        };
+        match action {
+            RefundAction::Offset => requests.extend(offset),
+            RefundAction::Reduce => updates.extend(original.id.zip(reduced)),
+            RefundAction::Skip => {}
+        }
+    }
+
+    report_skipped(&ambiguous, &unrecorded_refunds, &recorder.unmapped);
+
+    if let Some(path) = args.plan.as_deref() {
+        SyncPlan::new(group_id, &requests)?.save(path)?;
+        println!(
+            "Wrote {} proposed expenses to {}",
+            requests.len(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    // Create the expenses concurrently, within the client's rate limit
+    let descriptions: Vec<String> = requests.iter().map(|r| r.description.clone()).collect();
+    let results = client.expenses().create_expenses(requests).await;
+    for (description, result) in descriptions.iter().zip(results) {
+        if let Err(e) = result {
+            println!("Failed creating expense '{}': {}", description, e);
+        }
+    }
+    for (id, update) in updates {
+        if let Err(e) = client.expenses().update_expense(id, update).await {
+            println!("Failed reducing expense {}: {}", id, e);
+        }
+    }
+
+    print_dry_run(&client);
+    Ok(())
+}
+
+/// Creates the expenses of a plan file, and records their IDs in it.
+async fn apply_plan(client: &Client, path: &Path) -> Result<()> {
+    let mut plan = SyncPlan::load(path)?;
+    plan.apply(client).await?;
+
+    let created = plan
+        .expenses
         .iter()
-        .filter(|txn| txn.date.signed_duration_since(after_date).num_days() >= 0)
-        .filter(|txn| txn.date.signed_duration_since(before_date).num_days() <= 0)
-        .filter(|txn| txn.is_expense || args.all)
-        .filter(|txn| args.account.is_match(&txn.account_ref.name))
-        .filter(|txn| args.description.is_match(&txn.description))
-        .take(limit)
-        .filter(|txn| !expense_exists(&expenses, txn))
-        .filter(|txn| {
-            let prompt = format!(
-                "{}: {} @ [{}] {}  -- Sync?",
-                txn.date, txn.amount, txn.account_ref.name, txn.description
+        .filter(|e| e.expense_id.is_some())
+        .count();
+    println!(
+        "{} of {} planned expenses exist",
+        created,
+        plan.expenses.len()
+    );
+    for planned in plan.expenses.iter() {
+        if let Some(error) = planned.error.as_ref() {
+            println!(
+                "Failed creating expense '{}': {}",
+                planned.description, error
             );
-            Confirm::new()
-                .with_prompt(prompt)
-                .interact()
-                .unwrap_or(false)
-        })
-    {
-        if args.dry_run {
-            continue;
         }
+    }
 
-        let res = client
-            .expenses()
-            .create_expense(CreateExpenseRequest {
-                cost: format!("{:.2}", -t.amount),
-                description: t.description.clone(),
-                details: Some(format!("mint:{}", t.id)),
-                date: Utc.from_utc_datetime(&t.date.and_time(NaiveTime::default())),
-                repeat_interval: "never".to_string(),
-                currency_code: "USD".to_string(), // FIXME: Don't hardcode USD
-                category_id: 0,
-                group_id: args.group_id,
-                split_equally: true,
-                users: None,
-            })
-            .await;
-
-        if let Err(e) = res {
-            println!("Failed creating expense: {}", e);
+    // Dry runs create nothing, so there are no IDs to record
+    if client.plan().is_none() {
+        plan.save(path)?;
+    }
+    Ok(())
+}
+
+/// Reports transactions that need a closer look.
+fn report_skipped(
+    ambiguous: &[(&Transaction, Vec<(&Expense, f64)>)],
+    refunds: &[(&Transaction, &Expense)],
+    unmapped: &BTreeMap<String, usize>,
+) {
+    for (txn, candidates) in ambiguous.iter() {
+        println!(
+            "Skipped {}: {} @ [{}] {}, which could match several expenses:",
+            txn.date, txn.amount, txn.account, txn.description
+        );
+        for (expense, score) in candidates {
+            println!(
+                "  {} {} {} (score {:.2})",
+                expense.id.unwrap_or_default(),
+                expense.cost.as_deref().unwrap_or_default(),
+                expense.description.as_deref().unwrap_or_default(),
+                score
+            );
         }
     }
 
-    Ok(())
+    for (txn, original) in refunds.iter() {
+        println!(
+            "Skipped {}: {} @ [{}] {}, a refund of expense {} that could not be offset",
+            txn.date,
+            txn.amount,
+            txn.account,
+            txn.description,
+            original.id.unwrap_or_default()
+        );
+    }
+
+    if !unmapped.is_empty() {
+        println!("No Splitwise category found for these bank categories:");
+        for (category, count) in unmapped.iter() {
+            println!("  {} ({} transactions)", category, count);
+        }
+    }
+}
+
+/// Parses a number of requests per second, which must be positive.
+fn parse_rate(s: &str) -> Result<f64> {
+    let rate: f64 = s.parse()?;
+    if !rate.is_finite() || rate <= 0.0 {
+        bail!("rate must be a positive number, got '{}'", s)
+    }
+    Ok(rate)
+}
+
+/// Prints the requests a dry run would have sent.
+fn print_dry_run(client: &Client) {
+    if let Some(plan) = client.plan() {
+        for request in plan.requests() {
+            println!(
+                "Would send: {} {} {}",
+                request.method,
+                request.path,
+                request.redacted_body().unwrap_or_default()
+            );
+        }
+    }
+}
+
+/// Applies the date, account and description filters. Incomes are kept, since
+/// some may be refunds.
+fn filter_transactions(txns: Vec<Transaction>, args: &Args) -> Vec<Transaction> {
+    let after_date = args.after.unwrap_or(NaiveDate::MIN);
+    let before_date = args.before.unwrap_or(NaiveDate::MAX);
+    txns.into_iter()
+        .filter(|txn| txn.date >= after_date && txn.date <= before_date)
+        .filter(|txn| args.account.is_match(&txn.account))
+        .filter(|txn| args.description.is_match(&txn.description))
+        .collect()
+}
+
+/// Asks whether to sync a transaction.
+fn confirm(txn: &Transaction) -> bool {
+    let prompt = format!(
+        "{}: {} {} @ [{}] {}  -- Sync?",
+        txn.date, txn.amount, txn.currency, txn.account, txn.description
+    );
+    Confirm::new()
+        .with_prompt(prompt)
+        .interact()
+        .unwrap_or(false)
+}
+
+/// What to do about a refund of an existing expense.
+enum RefundAction {
+    /// Add an expense reversing part of the original one.
+    Offset,
+    /// Reduce the cost of the original expense.
+    Reduce,
+    Skip,
+}
+
+/// Asks what to do about a refund, explaining which expense it matched.
+fn choose_refund_action(
+    txn: &Transaction,
+    original: &Expense,
+    can_offset: bool,
+    can_reduce: bool,
+) -> RefundAction {
+    let prompt = format!(
+        "{}: refund of {} {} @ [{}] {}  -- matches '{}' from {} costing {}",
+        txn.date,
+        -txn.amount,
+        txn.currency,
+        txn.account,
+        txn.description,
+        original.description.as_deref().unwrap_or_default(),
+        original
+            .date
+            .map(|d| d.date_naive().to_string())
+            .unwrap_or_default(),
+        original.cost.as_deref().unwrap_or_default(),
+    );
+    let mut actions = Vec::new();
+    if can_offset {
+        actions.push((RefundAction::Offset, "Add an expense reversing its shares"));
+    }
+    if can_reduce {
+        actions.push((RefundAction::Reduce, "Reduce its cost"));
+    }
+    actions.push((RefundAction::Skip, "Skip"));
+
+    let items: Vec<&str> = actions.iter().map(|(_, item)| *item).collect();
+    let chosen = Select::new()
+        .with_prompt(prompt)
+        .items(&items)
+        .default(0)
+        .interact()
+        .unwrap_or(items.len() - 1);
+    actions
+        .into_iter()
+        .nth(chosen)
+        .map_or(RefundAction::Skip, |(action, _)| action)
+}
+
+/// Builds the expenses recording transactions in a group, applying the rules.
+struct Recorder {
+    group_id: i64,
+    rules: Rules,
+    categories: CategoryMap,
+    current_user_id: i64,
+    members: Vec<i64>,
+    /// Bank categories without a Splitwise category, with their counts
+    unmapped: BTreeMap<String, usize>,
+}
+
+impl Recorder {
+    async fn new(client: &Client, args: &Args, group_id: i64) -> Result<Self> {
+        let rules = match args.rules.as_deref() {
+            Some(path) => Rules::load(path)?,
+            None => Rules::default(),
+        };
+        let categories = client.other().get_categories().await?;
+        let categories = CategoryMap::new(&categories, args.category_map.as_deref())?;
+        // Only rules setting payers or splits need to know who is in the group
+        let (current_user_id, members) = if rules.is_empty() {
+            (0, Vec::new())
+        } else {
+            let user = client.users().get_current_user().await?;
+            let group = client.groups().get_group(group_id).await?;
+            let members = group
+                .members
+                .iter()
+                .flatten()
+                .filter_map(|m| m.id)
+                .collect();
+            (user.id.unwrap_or_default(), members)
+        };
+        Ok(Self {
+            group_id,
+            rules,
+            categories,
+            current_user_id,
+            members,
+            unmapped: BTreeMap::new(),
+        })
+    }
+
+    /// Builds the request creating an expense for the transaction, split
+    /// equally in the group and categorized like in the bank unless a rule
+    /// says otherwise.
+    fn expense_request(&mut self, txn: &Transaction) -> CreateExpenseRequest {
+        let outcome = self.rules.apply(txn);
+        let users = outcome.shares(txn.amount, self.current_user_id, &self.members);
+        let category_id = outcome.category_id.or_else(|| {
+            let id = self.categories.resolve(txn);
+            if let (None, Some(category)) = (id, txn.category.as_ref()) {
+                *self.unmapped.entry(category.clone()).or_default() += 1;
+            }
+            id
+        });
+        CreateExpenseRequest {
+            cost: format!("{:.2}", txn.amount),
+            description: outcome
+                .description
+                .clone()
+                .unwrap_or_else(|| txn.description.clone()),
+            details: Some(txn.id.clone()),
+            date: Utc.from_utc_datetime(&txn.date.and_time(NaiveTime::default())),
+            repeat_interval: "never".to_string(),
+            currency_code: txn.currency.clone(),
+            category_id: category_id.unwrap_or(0),
+            group_id: self.group_id,
+            split_equally: users.is_none(),
+            payment: None,
+            users,
+        }
+    }
 }
 
 /// Builds a Regex from the given pattern using smart case sensitivity. If the
 /// pattern contains any uppercase characters, then the Regex will be case
 /// sensitive, otherwise it will not.
-fn build_regex_smartcase(pattern: &str) -> Result<Regex> {
+pub(crate) fn build_regex_smartcase(pattern: &str) -> Result<Regex> {
     let has_uppercase = pattern.chars().any(|c| c.is_ascii_uppercase());
     let re = RegexBuilder::new(pattern)
         .case_insensitive(!has_uppercase)
@@ -141,54 +499,16 @@ fn build_regex_smartcase(pattern: &str) -> Result<Regex> {
     Ok(re)
 }
 
-fn read_mint_transactions_from_file<P: AsRef<Path>>(path: P) -> Result<Vec<MintTransaction>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let transactions = serde_json::from_reader(reader)?;
-    Ok(transactions)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-// TODO: Concept of strict vs fuzzy matching of transaction to expense. Making
-// a Splitwise expense could prepend `mint:<transaction_id>` to details, then
-// when listing it could be used as a key to build a hashmap for check.
-fn expense_exists(expenses: &[Expense], txn: &MintTransaction) -> bool {
-    let days_tolerance = 2;
-    let amount_tolerance = 1.0;
-
-    for expense in expenses {
-        // Short-circuit if the Mint transaction ID is found in the expense details
-        if expense.details.is_some()
-            && expense
-                .details
-                .clone()
-                .unwrap()
-                .contains(&format!("mint:{}", txn.id))
-        {
-            return true;
-        }
-
-        let expense_date = match expense.date {
-            Some(datetime) => datetime.date_naive(),
-            None => continue,
-        };
-        let expense_cost = match expense.cost.iter().flat_map(|s| s.parse::<f32>()).next() {
-            Some(cost) => cost,
-            None => continue,
-        };
-
-        let days_delta = txn
-            .date
-            .signed_duration_since(expense_date)
-            .num_days()
-            .saturating_abs();
-        let amount_delta = (-txn.amount - expense_cost as f64).abs();
-
-        // Check if the transaction roughly matches the expense
-        if days_delta < days_tolerance && amount_delta < amount_tolerance {
-            // FIXME: Fuzzy match on description
-            return true;
-        }
+    #[test]
+    fn parse_rate_rejects_non_positive_rates() {
+        assert_eq!(parse_rate("0.5").unwrap(), 0.5);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("NaN").is_err());
+        assert!(parse_rate("inf").is_err());
     }
-
-    false
 }
@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::mint::MintSource;
+use crate::ofx::OfxSource;
+use crate::statement::CsvProfile;
+use crate::statement::CsvSource;
+
+/// Bank transaction read from a statement, independent of its file format.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Transaction {
+    /// Stable external ID, prefixed with its source, eg `mint:<id>`. Stored in
+    /// the details of synced expenses so they are not synced twice.
+    pub id: String,
+    pub date: NaiveDate,
+    /// Money spent, so refunds and incomes are negative.
+    pub amount: Decimal,
+    /// ISO 4217 currency code of the amount.
+    pub currency: String,
+    pub account: String,
+    pub description: String,
+    /// Category assigned by the bank or aggregator, if any.
+    pub category: Option<String>,
+    /// Parent of the category, for aggregators with category trees.
+    pub category_parent: Option<String>,
+}
+
+impl Transaction {
+    pub fn is_expense(&self) -> bool {
+        self.amount.is_sign_positive() && !self.amount.is_zero()
+    }
+}
+
+/// Somewhere transactions can be read from, eg a statement file.
+pub(crate) trait TransactionSource {
+    /// Reads all transactions, in the order they appear in the source.
+    fn transactions(&self) -> Result<Vec<Transaction>>;
+}
+
+/// Opens the source for a statement file, based on its extension: OFX
+/// statements (`.ofx`, `.qfx`), CSV statements (`.csv`) read with the given
+/// column mapping profile, or else Mint JSON exports.
+pub(crate) fn open_source(
+    path: &Path,
+    csv_profile: Option<&str>,
+) -> Result<Box<dyn TransactionSource>> {
+    let path = PathBuf::from(path);
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("csv") => {
+            let profile = csv_profile.ok_or_else(|| {
+                anyhow!("CSV statements need --csv-profile: chase, mint, or a TOML profile path")
+            })?;
+            Ok(Box::new(CsvSource::new(path, CsvProfile::load(profile)?)))
+        }
+        Some("ofx") | Some("qfx") => Ok(Box::new(OfxSource::new(path))),
+        _ => Ok(Box::new(MintSource::new(path))),
+    }
+}
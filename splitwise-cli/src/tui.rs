@@ -0,0 +1,673 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use ratatui::crossterm::event;
+use ratatui::crossterm::event::Event;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Modifier;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Row;
+use ratatui::widgets::Table;
+use ratatui::widgets::TableState;
+use ratatui::widgets::Wrap;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use rust_decimal::Decimal;
+use splitwise::client::Client;
+use splitwise::model::comments::Comment;
+use splitwise::model::expenses::Expense;
+use splitwise::model::expenses::ListExpensesRequest;
+use splitwise::model::expenses::UpdateExpenseRequest;
+use splitwise::model::groups::Group;
+
+use crate::client::client;
+use crate::output::balances;
+use crate::output::cell;
+use crate::output::user_name;
+use crate::refunds::scaled_shares;
+
+/// Number of expenses loaded when opening a group.
+const EXPENSES_LIMIT: i64 = 200;
+
+pub(crate) async fn tui() -> Result<()> {
+    let client = client()?;
+    let mut app = App::new(&client).await?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+/// Screens, from the list of groups down to a single expense.
+enum Screen {
+    Groups {
+        groups: Vec<Group>,
+        state: TableState,
+    },
+    Expenses {
+        group: Box<Group>,
+        expenses: Vec<Expense>,
+        state: TableState,
+    },
+    Expense {
+        expense: Box<Expense>,
+        comments: Vec<Comment>,
+    },
+}
+
+/// What keys currently do.
+enum Mode {
+    Browse,
+    /// Asking whether to go ahead with a change
+    Confirm(Change),
+    Edit(EditForm),
+}
+
+/// Change to an expense, applied once confirmed.
+enum Change {
+    Delete(i64),
+    Restore(i64),
+    Update(i64, Box<UpdateExpenseRequest>),
+}
+
+impl Change {
+    fn prompt(&self) -> String {
+        match self {
+            Change::Delete(id) => format!("Delete expense {}?", id),
+            Change::Restore(id) => format!("Restore expense {}?", id),
+            Change::Update(id, _) => format!("Save changes to expense {}?", id),
+        }
+    }
+}
+
+struct App<'c> {
+    client: &'c Client,
+    current_user_id: Option<i64>,
+    screens: Vec<Screen>,
+    mode: Mode,
+    status: String,
+    quit: bool,
+}
+
+impl<'c> App<'c> {
+    async fn new(client: &'c Client) -> Result<Self> {
+        let current_user_id = client.users().get_current_user().await?.id;
+        let groups = client.groups().list_groups().await?;
+        Ok(Self {
+            client,
+            current_user_id,
+            screens: vec![Screen::Groups {
+                groups,
+                state: TableState::default().with_selected(0),
+            }],
+            mode: Mode::Browse,
+            status: String::new(),
+            quit: false,
+        })
+    }
+
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            // Reading blocks until a key is pressed, so keep it off the runtime
+            if let Event::Key(key) = tokio::task::spawn_blocking(event::read).await?? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                // Keep going on API errors, they are shown in the status line
+                if let Err(e) = self.handle_key(key).await {
+                    self.status = format!("Error: {:#}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.browse(key.code).await,
+            Mode::Confirm(change) => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => self.apply(change).await,
+                _ => {
+                    self.status = "Cancelled".to_string();
+                    Ok(())
+                }
+            },
+            Mode::Edit(mut form) => {
+                match key.code {
+                    KeyCode::Esc => self.status = "Cancelled".to_string(),
+                    KeyCode::Enter => {
+                        let request = self
+                            .selected_expense()
+                            .ok_or_else(|| anyhow!("no expense selected"))
+                            .and_then(|expense| form.request(expense));
+                        match request {
+                            Ok(request) => {
+                                self.mode = Mode::Confirm(Change::Update(
+                                    form.expense_id,
+                                    Box::new(request),
+                                ))
+                            }
+                            // Keep editing so the input can be fixed
+                            Err(e) => {
+                                self.mode = Mode::Edit(form);
+                                return Err(e);
+                            }
+                        }
+                    }
+                    code => {
+                        form.handle_key(code);
+                        self.mode = Mode::Edit(form);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn browse(&mut self, code: KeyCode) -> Result<()> {
+        self.status.clear();
+        match code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Left if self.screens.len() > 1 => {
+                self.screens.pop();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Enter | KeyCode::Right => self.open().await?,
+            KeyCode::Char('d') => {
+                if let Some(id) = self.selected_expense().and_then(|e| e.id) {
+                    self.mode = Mode::Confirm(Change::Delete(id));
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(id) = self.selected_expense().and_then(|e| e.id) {
+                    self.mode = Mode::Confirm(Change::Restore(id));
+                }
+            }
+            KeyCode::Char('e') => {
+                if let Some(expense) = self.selected_expense() {
+                    self.mode = Mode::Edit(EditForm::new(expense));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn move_selection(&mut self, delta: i64) {
+        let (state, len) = match self.screens.last_mut() {
+            Some(Screen::Groups { groups, state }) => (state, groups.len()),
+            Some(Screen::Expenses {
+                expenses, state, ..
+            }) => (state, expenses.len()),
+            _ => return,
+        };
+        if len == 0 {
+            return;
+        }
+        let selected = state.selected().unwrap_or(0) as i64 + delta;
+        state.select(Some(selected.clamp(0, len as i64 - 1) as usize));
+    }
+
+    /// Drills into the selected group or expense.
+    async fn open(&mut self) -> Result<()> {
+        let screen = match self.screens.last() {
+            Some(Screen::Groups { groups, state }) => {
+                let Some(group) = state.selected().and_then(|i| groups.get(i)) else {
+                    return Ok(());
+                };
+                Screen::Expenses {
+                    expenses: self.group_expenses(group).await?,
+                    group: Box::new(group.clone()),
+                    state: TableState::default().with_selected(0),
+                }
+            }
+            Some(Screen::Expenses {
+                expenses, state, ..
+            }) => {
+                let Some(expense) = state.selected().and_then(|i| expenses.get(i)) else {
+                    return Ok(());
+                };
+                let comments = match expense.id {
+                    Some(id) => self.client.comments().get_comments(id).await?,
+                    None => Vec::new(),
+                };
+                Screen::Expense {
+                    expense: Box::new(expense.clone()),
+                    comments,
+                }
+            }
+            _ => return Ok(()),
+        };
+        self.screens.push(screen);
+        Ok(())
+    }
+
+    async fn group_expenses(&self, group: &Group) -> Result<Vec<Expense>> {
+        self.client
+            .expenses()
+            .list_expenses(ListExpensesRequest {
+                group_id: group.id,
+                limit: Some(EXPENSES_LIMIT),
+                ..ListExpensesRequest::default()
+            })
+            .await
+    }
+
+    fn selected_expense(&self) -> Option<&Expense> {
+        match self.screens.last()? {
+            Screen::Expenses {
+                expenses, state, ..
+            } => expenses.get(state.selected()?),
+            Screen::Expense { expense, .. } => Some(expense),
+            Screen::Groups { .. } => None,
+        }
+    }
+
+    /// Applies a confirmed change, then reloads the expenses and balances
+    /// shown.
+    async fn apply(&mut self, change: Change) -> Result<()> {
+        let expenses = self.client.expenses();
+        let id = match change {
+            Change::Delete(id) => {
+                expenses.delete_expense(id).await?;
+                self.status = format!("Deleted expense {}", id);
+                id
+            }
+            Change::Restore(id) => {
+                expenses.restore_expense(id).await?;
+                self.status = format!("Restored expense {}", id);
+                id
+            }
+            Change::Update(id, request) => {
+                expenses.update_expense(id, *request).await?;
+                self.status = format!("Saved expense {}", id);
+                id
+            }
+        };
+
+        let updated = expenses.get_expense(id).await?;
+        for screen in self.screens.iter_mut() {
+            match screen {
+                Screen::Expenses { expenses, .. } => {
+                    for expense in expenses.iter_mut().filter(|e| e.id == Some(id)) {
+                        *expense = updated.clone();
+                    }
+                }
+                Screen::Expense { expense, .. } if expense.id == Some(id) => {
+                    **expense = updated.clone();
+                }
+                _ => {}
+            }
+        }
+        // Balances change with the expenses
+        let groups = self.client.groups().list_groups().await?;
+        for screen in self.screens.iter_mut() {
+            if let Screen::Expenses { group, .. } = screen {
+                if let Some(updated) = groups.iter().find(|g| g.id == group.id) {
+                    **group = updated.clone();
+                }
+            }
+        }
+        if let Some(Screen::Groups { groups: shown, .. }) = self.screens.first_mut() {
+            *shown = groups;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let current_user_id = self.current_user_id;
+
+        let hints = match self.screens.last_mut() {
+            Some(Screen::Groups { groups, state }) => {
+                frame.render_stateful_widget(groups_table(groups, current_user_id), main, state);
+                "enter: expenses  q: quit"
+            }
+            Some(Screen::Expenses {
+                group,
+                expenses,
+                state,
+            }) => {
+                let table = expenses_table(group, expenses, current_user_id);
+                frame.render_stateful_widget(table, main, state);
+                "enter: details  e: edit  d: delete  r: restore  esc: back  q: quit"
+            }
+            Some(Screen::Expense { expense, comments }) => {
+                draw_expense(frame, main, expense, comments);
+                "e: edit  d: delete  r: restore  esc: back  q: quit"
+            }
+            None => "",
+        };
+        let footer_text = if self.status.is_empty() {
+            hints.to_string()
+        } else {
+            self.status.clone()
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+
+        match &self.mode {
+            Mode::Browse => {}
+            Mode::Confirm(change) => {
+                let area = popup(frame.area(), 50, 3);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(format!("{} (y/n)", change.prompt())).block(Block::bordered()),
+                    area,
+                );
+            }
+            Mode::Edit(form) => {
+                let area = popup(frame.area(), 60, form.fields.len() as u16 + 2);
+                frame.render_widget(Clear, area);
+                let lines: Vec<Line> = form
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (label, value))| {
+                        let line = Line::from(format!("{:>12}: {}", label, value));
+                        if i == form.focus {
+                            line.style(Style::new().add_modifier(Modifier::REVERSED))
+                        } else {
+                            line
+                        }
+                    })
+                    .collect();
+                let block = Block::bordered().title("Edit expense (tab: next, enter: save)");
+                frame.render_widget(Paragraph::new(lines).block(block), area);
+            }
+        }
+    }
+}
+
+fn groups_table(groups: &[Group], current_user_id: Option<i64>) -> Table<'static> {
+    let rows = groups.iter().map(|group| {
+        let balance = group
+            .members
+            .iter()
+            .flatten()
+            .find(|m| m.id == current_user_id)
+            .map(|m| balances(m.balance.as_ref()))
+            .unwrap_or_default();
+        Row::new(vec![
+            cell(group.name.as_ref()),
+            balance,
+            cell(group.members.as_ref().map(|m| m.len())),
+        ])
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(24),
+            Constraint::Length(8),
+        ],
+    )
+    .header(header(&["Group", "Your balance", "Members"]))
+    .block(Block::bordered().title("Groups"))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn expenses_table(
+    group: &Group,
+    expenses: &[Expense],
+    current_user_id: Option<i64>,
+) -> Table<'static> {
+    let rows = expenses.iter().map(|expense| {
+        let share = expense
+            .users
+            .iter()
+            .flatten()
+            .find(|s| s.user_id() == current_user_id);
+        let row = Row::new(vec![
+            cell(expense.date.map(|d| d.date_naive())),
+            cell(expense.description.as_ref()),
+            format!(
+                "{} {}",
+                expense.cost.as_deref().unwrap_or_default(),
+                expense.currency_code.as_deref().unwrap_or_default()
+            ),
+            cell(share.and_then(|s| s.owed_share.as_ref())),
+        ]);
+        if expense.deleted_at.is_some() {
+            row.style(Style::new().add_modifier(Modifier::CROSSED_OUT | Modifier::DIM))
+        } else {
+            row
+        }
+    });
+    Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Fill(1),
+            Constraint::Length(14),
+            Constraint::Length(10),
+        ],
+    )
+    .header(header(&["Date", "Description", "Cost", "Your share"]))
+    .block(Block::bordered().title(format!(
+        "Expenses in {}",
+        group.name.as_deref().unwrap_or_default()
+    )))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+}
+
+fn draw_expense(frame: &mut Frame, area: Rect, expense: &Expense, comments: &[Comment]) {
+    let shares = expense.users.as_ref().map_or(0, |u| u.len()) as u16;
+    let [summary, shares_area, comments_area] = Layout::vertical([
+        Constraint::Length(6),
+        Constraint::Length(shares + 3),
+        Constraint::Min(0),
+    ])
+    .areas(area);
+
+    let mut lines = vec![
+        Line::from(format!(
+            "{} {}",
+            expense.cost.as_deref().unwrap_or_default(),
+            expense.currency_code.as_deref().unwrap_or_default()
+        )),
+        Line::from(format!(
+            "Date: {}",
+            cell(expense.date.map(|d| d.date_naive()))
+        )),
+        Line::from(format!(
+            "Category: {}",
+            cell(expense.category.as_ref().and_then(|c| c.name.as_ref()))
+        )),
+    ];
+    if let Some(deleted_at) = expense.deleted_at {
+        lines.push(Line::from(format!(
+            "Deleted on {}",
+            deleted_at.date_naive()
+        )));
+    }
+    if let Some(details) = expense.details.as_deref().filter(|d| !d.is_empty()) {
+        lines.push(Line::from(format!("Notes: {}", details)));
+    }
+    let title = expense.description.clone().unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        summary,
+    );
+
+    let rows = expense.users.iter().flatten().map(|share| {
+        Row::new(vec![
+            share.user.as_ref().map(user_name).unwrap_or_default(),
+            cell(share.paid_share.as_ref()),
+            cell(share.owed_share.as_ref()),
+            cell(share.net_balance.as_ref()),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+        ],
+    )
+    .header(header(&["User", "Paid", "Owed", "Net"]))
+    .block(Block::bordered().title("Shares"));
+    frame.render_widget(table, shares_area);
+
+    let lines: Vec<Line> = comments
+        .iter()
+        .map(|c| {
+            Line::from(format!(
+                "{} {}: {}",
+                c.created_at.date_naive(),
+                c.user.as_ref().map(user_name).unwrap_or_default(),
+                c.content
+            ))
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: true })
+            .block(Block::bordered().title("Comments")),
+        comments_area,
+    );
+}
+
+fn header(titles: &[&'static str]) -> Row<'static> {
+    Row::new(titles.to_vec()).style(Style::new().add_modifier(Modifier::BOLD))
+}
+
+/// Centered area of the given width percentage and height.
+fn popup(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)])
+        .flex(ratatui::layout::Flex::Center)
+        .areas(area);
+    area
+}
+
+/// Description, cost and date of an expense being edited.
+struct EditForm {
+    expense_id: i64,
+    fields: [(&'static str, String); 3],
+    focus: usize,
+}
+
+impl EditForm {
+    fn new(expense: &Expense) -> Self {
+        Self {
+            expense_id: expense.id.unwrap_or_default(),
+            fields: [
+                (
+                    "Description",
+                    expense.description.clone().unwrap_or_default(),
+                ),
+                ("Cost", expense.cost.clone().unwrap_or_default()),
+                ("Date", cell(expense.date.map(|d| d.date_naive()))),
+            ],
+            focus: 0,
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % self.fields.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                self.focus = (self.focus + self.fields.len() - 1) % self.fields.len()
+            }
+            KeyCode::Backspace => {
+                self.fields[self.focus].1.pop();
+            }
+            KeyCode::Char(c) => self.fields[self.focus].1.push(c),
+            _ => {}
+        }
+    }
+
+    /// Builds the update of the changed fields. Shares are scaled to a new
+    /// cost, so that they still add up.
+    fn request(&self, expense: &Expense) -> Result<UpdateExpenseRequest> {
+        let [(_, description), (_, cost), (_, date)] = &self.fields;
+        let mut request = UpdateExpenseRequest::default();
+
+        if Some(description) != expense.description.as_ref() {
+            request.description = Some(description.clone());
+        }
+        let cost =
+            Decimal::from_str(cost.trim()).map_err(|_| anyhow!("invalid cost '{}'", cost))?;
+        let current = expense
+            .cost
+            .as_deref()
+            .and_then(|c| Decimal::from_str(c).ok());
+        if Some(cost) != current {
+            request.cost = Some(format!("{:.2}", cost));
+            request.users = scaled_shares(expense, cost, false);
+        }
+        let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map_err(|_| anyhow!("invalid date '{}', expected YYYY-MM-DD", date))?;
+        if Some(date) != expense.date.map(|d| d.date_naive()) {
+            request.date = Some(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()));
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn edit_form_sends_changed_fields_with_scaled_shares() {
+        let expense: Expense = serde_json::from_value(json!({
+            "id": 1, "group_id": 5, "date": "2023-03-01T00:00:00Z", "cost": "30.00", "description": "Dinner",
+            "users": [
+                {"user_id": 1, "paid_share": "30.00", "owed_share": "15.00"},
+                {"user_id": 2, "paid_share": "0.00", "owed_share": "15.00"}
+            ]
+        }))
+        .unwrap();
+
+        let mut form = EditForm::new(&expense);
+        let request = form.request(&expense).unwrap();
+        assert_eq!(request.description, None);
+        assert_eq!(request.cost, None);
+        assert_eq!(request.date, None);
+
+        form.handle_key(KeyCode::Tab);
+        for _ in 0..5 {
+            form.handle_key(KeyCode::Backspace);
+        }
+        for c in "40".chars() {
+            form.handle_key(KeyCode::Char(c));
+        }
+        let request = form.request(&expense).unwrap();
+        assert_eq!(request.group_id, None);
+        assert_eq!(request.cost.as_deref(), Some("40.00"));
+        let owed: Vec<&str> = request
+            .users
+            .iter()
+            .flatten()
+            .map(|s| s.owed_share.as_deref().unwrap())
+            .collect();
+        assert_eq!(owed, vec!["20.00", "20.00"]);
+
+        form.handle_key(KeyCode::Char('x'));
+        assert!(form.request(&expense).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::client::client;
+use crate::output::cell;
+use crate::output::user_name;
+use crate::output::OutputArgs;
+use crate::output::Table;
+
+#[derive(Parser)]
+pub(crate) struct MeArgs {
+    #[clap(flatten)]
+    output: OutputArgs,
+}
+
+pub(crate) async fn me(args: MeArgs) -> Result<()> {
+    let user = client()?.users().get_current_user().await?;
+    args.output.print(&user, |user| {
+        Table::fields(&[
+            ("ID", cell(user.id)),
+            ("Name", user_name(user)),
+            ("Email", cell(user.email.as_ref())),
+            ("Currency", cell(user.default_currency.as_ref())),
+            ("Locale", cell(user.locale.as_ref())),
+        ])
+    })
+}
@@ -12,12 +12,13 @@ async fn main() {
         .expenses()
         .list_expenses(ListExpensesRequest {
             group_id: Some(group_id),
-            dated_after: Some(chrono::Utc.ymd(2022, 1, 1).and_hms(0, 0, 0)),
+            dated_after: Some(chrono::Utc.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap()),
             ..ListExpensesRequest::default()
         })
         .await
         .unwrap();
 
+    let mut to_delete = Vec::new();
     for expense in expenses.iter() {
         let id = expense.id.unwrap();
         let date = expense.date.unwrap();
@@ -34,7 +35,15 @@ async fn main() {
             continue;
         }
 
-        client.expenses().delete_expense(id).await.unwrap();
-        println!("Successfully deleted expense");
+        to_delete.push(id);
+    }
+
+    // Deletions are sent concurrently, within the default rate limit
+    let results = client.expenses().delete_expenses(to_delete.clone()).await;
+    for (id, result) in to_delete.iter().zip(results) {
+        match result {
+            Ok(_) => println!("Successfully deleted expense {}", id),
+            Err(e) => println!("Error deleting expense {}: {}", id, e),
+        }
     }
 }
@@ -53,7 +53,7 @@ async fn main() {
         .expenses()
         .list_expenses(ListExpensesRequest {
             group_id: Some(group_id),
-            dated_after: Some(chrono::Utc.ymd(2021, 12, 31).and_hms(0, 0, 0)),
+            dated_after: Some(chrono::Utc.with_ymd_and_hms(2021, 12, 31, 0, 0, 0).unwrap()),
             ..ListExpensesRequest::default()
         })
         .await
@@ -72,10 +72,10 @@ async fn main() {
         // Check is expense already exists
         // TODO: Big bad nested loop, fix this
         for expense in expenses.iter() {
-            if expense.id == None
-                || expense.date == None
-                || expense.description == None
-                || expense.cost == None
+            if expense.id.is_none()
+                || expense.date.is_none()
+                || expense.description.is_none()
+                || expense.cost.is_none()
             {
                 continue;
             }
@@ -108,10 +108,7 @@ async fn main() {
         //     continue;
         // }
 
-        let date = Utc
-            .from_local_date(&txn.transaction_date)
-            .unwrap()
-            .and_hms(0, 0, 0)
+        let date = Utc.from_utc_datetime(&txn.transaction_date.and_hms_opt(0, 0, 0).unwrap())
             + chrono::Duration::days(1);
         let res = client
             .expenses()
@@ -125,6 +122,7 @@ async fn main() {
                 category_id: 0,
                 group_id,
                 split_equally: true,
+                payment: None,
                 users: None,
             })
             .await;
@@ -0,0 +1,412 @@
+//! Full backups of a Splitwise account, and restoring them into another one.
+//!
+//! An `Archive` is a versioned snapshot of everything visible to the current
+//! user, meant to be stored as JSON. Importing an archive recreates its
+//! friends, groups, expenses and user comments in whatever account the client
+//! is authenticated as. Since the new records get new IDs, the import returns
+//! a report mapping archived IDs to created ones.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use anyhow::bail;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::client::Client;
+use crate::model::comments::Comment;
+use crate::model::expenses::CreateExpenseRequest;
+use crate::model::expenses::Expense;
+use crate::model::expenses::ListExpensesRequest;
+use crate::model::friends::AddFriendsRequest;
+use crate::model::groups::Group;
+use crate::model::groups::GroupCreateRequest;
+use crate::model::groups::GroupUser;
+use crate::model::notifications::GetNotificationsRequest;
+use crate::model::notifications::Notification;
+use crate::model::users::User;
+
+/// Version of the archive format written by `export`. Archives with a newer
+/// version are rejected by `import`.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Snapshot of a Splitwise account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    /// Archive format version.
+    pub version: u32,
+
+    /// When the archive was exported.
+    pub exported_at: DateTime<Utc>,
+
+    /// User that exported the archive.
+    pub current_user: User,
+
+    /// Friends of the current user, with their balances.
+    pub friends: Vec<User>,
+
+    /// Groups of the current user, with their members and debts.
+    pub groups: Vec<Group>,
+
+    /// Every expense of the current user, including deleted ones.
+    pub expenses: Vec<Expense>,
+
+    /// Comments on expenses, by expense ID.
+    pub comments: BTreeMap<i64, Vec<Comment>>,
+
+    /// Recent activity on the account.
+    pub notifications: Vec<Notification>,
+}
+
+/// Expense, or other record, from an archive that was not imported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedExpense {
+    /// Archived ID of the record.
+    pub id: i64,
+
+    /// Why the record was not imported.
+    pub reason: String,
+}
+
+/// Result of `import`, mapping archived IDs to the IDs of created records.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Archived user IDs to user IDs in the target account.
+    pub users: BTreeMap<i64, i64>,
+
+    /// Archived group IDs to created group IDs.
+    pub groups: BTreeMap<i64, i64>,
+
+    /// Archived expense IDs to created expense IDs.
+    pub expenses: BTreeMap<i64, i64>,
+
+    /// Number of comments recreated.
+    pub comments: usize,
+
+    /// Friends that could not be added. Expenses shared with them are
+    /// skipped too.
+    #[serde(default)]
+    pub skipped_friends: Vec<SkippedExpense>,
+
+    /// Expenses that were not imported.
+    #[serde(default)]
+    pub skipped_expenses: Vec<SkippedExpense>,
+
+    /// Groups that could not be created. Their expenses are skipped too.
+    #[serde(default)]
+    pub skipped_groups: Vec<SkippedExpense>,
+
+    /// Comments that could not be recreated.
+    #[serde(default)]
+    pub skipped_comments: Vec<SkippedExpense>,
+}
+
+/// Exports everything visible to the current user into an archive.
+pub async fn export(client: &Client) -> Result<Archive, anyhow::Error> {
+    let current_user = client.users().get_current_user().await?;
+    let friends = client.friends().list_friends().await?;
+    let groups = client.groups().list_groups().await?;
+    let expenses = client
+        .expenses()
+        .list_all_expenses(ListExpensesRequest::default())
+        .await?;
+    let notifications = client
+        .notifications()
+        .get_notifications(GetNotificationsRequest::default())
+        .await?;
+
+    let mut comments = BTreeMap::new();
+    for expense in expenses.iter() {
+        let id = match expense.id {
+            Some(id) if expense.comments_count.unwrap_or(0) > 0 => id,
+            _ => continue,
+        };
+        comments.insert(id, client.comments().get_comments(id).await?);
+    }
+
+    Ok(Archive {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        current_user,
+        friends,
+        groups,
+        expenses,
+        comments,
+        notifications,
+    })
+}
+
+/// Recreates the friends, groups, expenses and user comments of an archive in
+/// the current user's account. Deleted expenses, and expenses involving users
+/// that could not be found in the target account, are skipped. Friends, groups,
+/// expenses and comments that fail to be created are recorded as skipped in
+/// the report rather than aborting the import, so that the IDs of what was
+/// created are not lost. Recurring expenses are recreated as one-off
+/// expenses, since their past occurrences are in the archive too.
+pub async fn import(client: &Client, archive: &Archive) -> Result<ImportReport, anyhow::Error> {
+    if archive.version > ARCHIVE_VERSION {
+        bail!(
+            "archive version {} is newer than the supported version {}",
+            archive.version,
+            ARCHIVE_VERSION
+        )
+    }
+
+    let mut report = ImportReport::default();
+    let current_user = client.users().get_current_user().await?;
+    if let (Some(old), Some(new)) = (archive.current_user.id, current_user.id) {
+        report.users.insert(old, new);
+    }
+
+    // Friends must exist before they can share non-group expenses
+    let emails: Vec<String> = archive
+        .friends
+        .iter()
+        .filter_map(|f| f.email.clone())
+        .collect();
+    if !emails.is_empty() {
+        let added = client
+            .friends()
+            .add_friends(AddFriendsRequest {
+                emails,
+                allow_partial_success: Some(true),
+                ..AddFriendsRequest::default()
+            })
+            .await;
+        match added {
+            Ok(added) => map_users(&mut report, &archive.friends, added.users.iter().flatten()),
+            Err(e) => {
+                let reason = e.to_string();
+                report.skipped_friends = archive
+                    .friends
+                    .iter()
+                    .filter(|f| f.email.is_some())
+                    .filter_map(|f| f.id)
+                    .map(|id| SkippedExpense {
+                        id,
+                        reason: reason.clone(),
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    for group in archive.groups.iter() {
+        // Group 0 holds expenses that are not in any group
+        let old_id = match group.id {
+            Some(id) if id != 0 => id,
+            _ => continue,
+        };
+        let members: Vec<&User> = group
+            .members
+            .iter()
+            .flatten()
+            .filter(|m| m.id != archive.current_user.id)
+            .collect();
+        let created = client
+            .groups()
+            .create_group(GroupCreateRequest {
+                name: group.name.clone().unwrap_or_default(),
+                group_type: group.group_type.clone(),
+                simplify_by_default: group.simplify_by_default,
+                users: Some(
+                    members
+                        .iter()
+                        .map(|m| GroupUser {
+                            user_id: None,
+                            first_name: m.first_name.clone(),
+                            last_name: m.last_name.clone(),
+                            email: m.email.clone(),
+                        })
+                        .collect(),
+                ),
+            })
+            .await;
+        let created = match created {
+            Ok(created) => created,
+            Err(e) => {
+                report.skipped_groups.push(SkippedExpense {
+                    id: old_id,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if let Some(new_id) = created.id {
+            report.groups.insert(old_id, new_id);
+        }
+        let archived: Vec<User> = members.into_iter().cloned().collect();
+        map_users(&mut report, &archived, created.members.iter().flatten());
+    }
+
+    let mut requests = Vec::new();
+    let mut old_ids = Vec::new();
+    for expense in archive.expenses.iter() {
+        let id = match expense.id {
+            Some(id) => id,
+            None => continue,
+        };
+        match remap_expense(&report, expense) {
+            Ok(request) => {
+                old_ids.push(id);
+                requests.push(request);
+            }
+            Err(reason) => report.skipped_expenses.push(SkippedExpense { id, reason }),
+        }
+    }
+
+    let results = client.expenses().create_expenses(requests).await;
+    for (old_id, result) in old_ids.into_iter().zip(results) {
+        let new_id = match result.map(|created| created.first().and_then(|e| e.id)) {
+            Ok(Some(new_id)) => new_id,
+            Ok(None) => {
+                report.skipped_expenses.push(SkippedExpense {
+                    id: old_id,
+                    reason: "no expense was created".to_string(),
+                });
+                continue;
+            }
+            Err(e) => {
+                report.skipped_expenses.push(SkippedExpense {
+                    id: old_id,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        report.expenses.insert(old_id, new_id);
+
+        for comment in archive.comments.get(&old_id).into_iter().flatten() {
+            // System comments are generated by Splitwise itself
+            if comment.comment_type != "User" || comment.deleted_at.is_some() {
+                continue;
+            }
+            match client
+                .comments()
+                .create_comment(new_id, comment.content.clone())
+                .await
+            {
+                Ok(_) => report.comments += 1,
+                Err(e) => report.skipped_comments.push(SkippedExpense {
+                    id: comment.id,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Records the IDs of created users, matching them to archived users by email,
+/// or by name if they have no email.
+fn map_users<'a>(
+    report: &mut ImportReport,
+    archived: &[User],
+    created: impl Iterator<Item = &'a User>,
+) {
+    let identity = |u: &User| match u.email {
+        Some(ref email) => email.to_lowercase(),
+        None => format!(
+            "{} {}",
+            u.first_name.as_deref().unwrap_or_default(),
+            u.last_name.as_deref().unwrap_or_default()
+        ),
+    };
+    let created: HashMap<String, i64> = created
+        .filter_map(|u| u.id.map(|id| (identity(u), id)))
+        .collect();
+    for user in archived {
+        if let (Some(old), Some(new)) = (user.id, created.get(&identity(user))) {
+            report.users.insert(old, *new);
+        }
+    }
+}
+
+/// Builds the request recreating an archived expense with the IDs of the
+/// target account, or explains why it cannot be recreated.
+fn remap_expense(report: &ImportReport, expense: &Expense) -> Result<CreateExpenseRequest, String> {
+    if expense.deleted_at.is_some() {
+        return Err("expense is deleted".to_string());
+    }
+
+    let mut request = CreateExpenseRequest::from(expense);
+    // Past occurrences are archived, so they must not keep recurring
+    request.repeat_interval = "never".to_string();
+    if request.group_id != 0 {
+        request.group_id = *report
+            .groups
+            .get(&request.group_id)
+            .ok_or_else(|| format!("group {} was not imported", request.group_id))?;
+    }
+    for share in request.users.iter_mut().flatten() {
+        let old = share.user_id.ok_or("share has no user")?;
+        let new = report
+            .users
+            .get(&old)
+            .ok_or_else(|| format!("user {} was not found in the target account", old))?;
+        share.user_id = Some(*new);
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn remap_expense_uses_target_ids() {
+        let report = ImportReport {
+            users: [(1, 101), (2, 102)].into_iter().collect(),
+            groups: [(10, 110)].into_iter().collect(),
+            ..ImportReport::default()
+        };
+        let expense: Expense = serde_json::from_value(json!({
+            "id": 5,
+            "group_id": 10,
+            "cost": "10.00",
+            "repeat_interval": "monthly",
+            "users": [
+                {"user_id": 1, "paid_share": "10.00", "owed_share": "5.00"},
+                {"user": {"id": 2}, "paid_share": "0.00", "owed_share": "5.00"}
+            ]
+        }))
+        .unwrap();
+
+        let request = remap_expense(&report, &expense).unwrap();
+        assert_eq!(request.group_id, 110);
+        assert_eq!(request.repeat_interval, "never");
+        let users: Vec<i64> = request
+            .users
+            .unwrap()
+            .iter()
+            .map(|s| s.user_id.unwrap())
+            .collect();
+        assert_eq!(users, vec![101, 102]);
+    }
+
+    #[test]
+    fn remap_expense_rejects_unknown_users_and_deleted() {
+        let report = ImportReport::default();
+        let unknown: Expense = serde_json::from_value(json!({
+            "id": 5,
+            "users": [{"user_id": 3, "paid_share": "1.00", "owed_share": "1.00"}]
+        }))
+        .unwrap();
+        assert!(remap_expense(&report, &unknown).is_err());
+
+        let deleted: Expense = serde_json::from_value(json!({
+            "id": 6,
+            "deleted_at": "2023-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!(
+            remap_expense(&report, &deleted).unwrap_err(),
+            "expense is deleted"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Bulk edits of the expenses matching a filter.
+//!
+//! `select_expenses` lists the expenses an operation would change, so they can
+//! be previewed, and `apply_bulk_operation` applies it to each of them
+//! concurrently, reporting a result per expense.
+
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::Utc;
+use regex::Regex;
+use rust_decimal::Decimal;
+
+use crate::client::Client;
+use crate::model::expenses::Expense;
+use crate::model::expenses::ListExpensesRequest;
+use crate::model::expenses::UpdateExpenseRequest;
+
+/// Selects the expenses changed by a bulk operation. Unset fields match any
+/// expense.
+#[derive(Default, Debug, Clone)]
+pub struct ExpenseFilter {
+    /// Only expenses in this group, or 0 for expenses outside of groups.
+    pub group_id: Option<i64>,
+
+    /// Only expenses dated after this date.
+    pub dated_after: Option<DateTime<Utc>>,
+
+    /// Only expenses dated before this date.
+    pub dated_before: Option<DateTime<Utc>>,
+
+    /// Only expenses whose description matches this pattern.
+    pub description: Option<Regex>,
+
+    /// Only expenses in this category.
+    pub category_id: Option<i64>,
+
+    /// Only expenses paid, at least partly, by this user.
+    pub paid_by: Option<i64>,
+
+    /// Only expenses costing at least this amount.
+    pub min_cost: Option<Decimal>,
+
+    /// Only expenses costing at most this amount.
+    pub max_cost: Option<Decimal>,
+}
+
+impl ExpenseFilter {
+    /// Whether the filter has no condition, and so selects every expense.
+    pub fn is_empty(&self) -> bool {
+        self.group_id.is_none()
+            && self.dated_after.is_none()
+            && self.dated_before.is_none()
+            && self.description.is_none()
+            && self.category_id.is_none()
+            && self.paid_by.is_none()
+            && self.min_cost.is_none()
+            && self.max_cost.is_none()
+    }
+
+    /// Whether an expense is selected by the filter. Dates are compared too,
+    /// although `select_expenses` already filters on them when listing.
+    pub fn matches(&self, expense: &Expense) -> bool {
+        if let Some(group_id) = self.group_id {
+            if expense.group_id.unwrap_or_default() != group_id {
+                return false;
+            }
+        }
+        if let Some(after) = self.dated_after {
+            if expense.date.map_or(true, |d| d < after) {
+                return false;
+            }
+        }
+        if let Some(before) = self.dated_before {
+            if expense.date.map_or(true, |d| d > before) {
+                return false;
+            }
+        }
+        if let Some(pattern) = self.description.as_ref() {
+            if !pattern.is_match(expense.description.as_deref().unwrap_or_default()) {
+                return false;
+            }
+        }
+        if let Some(category_id) = self.category_id {
+            let id = expense
+                .category_id
+                .or_else(|| expense.category.as_ref().and_then(|c| c.id));
+            if id != Some(category_id) {
+                return false;
+            }
+        }
+        if let Some(user_id) = self.paid_by {
+            let paid = expense.users.iter().flatten().any(|share| {
+                share.user_id() == Some(user_id)
+                    && amount(share.paid_share.as_deref()).map_or(false, |p| p > Decimal::ZERO)
+            });
+            if !paid {
+                return false;
+            }
+        }
+        if self.min_cost.is_some() || self.max_cost.is_some() {
+            let cost = match amount(expense.cost.as_deref()) {
+                Some(cost) => cost,
+                None => return false,
+            };
+            if self.min_cost.map_or(false, |min| cost < min)
+                || self.max_cost.map_or(false, |max| cost > max)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Change applied to every selected expense.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkOperation {
+    /// Delete the expenses.
+    Delete,
+    /// Restore deleted expenses.
+    Restore,
+    /// Move the expenses to the category with this ID.
+    Recategorize(i64),
+    /// Move the expenses to the group with this ID, or out of any group with
+    /// 0, keeping their shares.
+    Move(i64),
+    /// Change the date of the expenses.
+    SetDate(DateTime<Utc>),
+    /// Change the currency code of the expenses, keeping their cost.
+    SetCurrency(String),
+}
+
+impl BulkOperation {
+    /// Whether the operation applies to an expense: only deleted expenses
+    /// can be restored, and only live ones changed.
+    pub fn applies_to(&self, expense: &Expense) -> bool {
+        match self {
+            BulkOperation::Restore => expense.deleted_at.is_some(),
+            _ => expense.deleted_at.is_none(),
+        }
+    }
+
+    /// Request updating an expense, for operations that only change fields of
+    /// the expense.
+    fn update_request(&self) -> UpdateExpenseRequest {
+        let mut request = UpdateExpenseRequest::default();
+        match self {
+            BulkOperation::Delete | BulkOperation::Restore | BulkOperation::Move(_) => {}
+            BulkOperation::Recategorize(category_id) => request.category_id = Some(*category_id),
+            BulkOperation::SetDate(date) => request.date = Some(*date),
+            BulkOperation::SetCurrency(code) => request.currency_code = Some(code.clone()),
+        }
+        request
+    }
+}
+
+/// Lists the expenses matching the filter that the operation applies to, for
+/// previewing before `apply_bulk_operation`.
+pub async fn select_expenses(
+    client: &Client,
+    filter: &ExpenseFilter,
+    operation: &BulkOperation,
+) -> Result<Vec<Expense>, anyhow::Error> {
+    let expenses = client
+        .expenses()
+        .list_all_expenses(ListExpensesRequest {
+            group_id: filter.group_id,
+            dated_after: filter.dated_after,
+            dated_before: filter.dated_before,
+            ..ListExpensesRequest::default()
+        })
+        .await?;
+    Ok(expenses
+        .into_iter()
+        .filter(|e| filter.matches(e) && operation.applies_to(e))
+        .collect())
+}
+
+/// Applies the operation to each expense concurrently, within the client's
+/// rate limit (or the default `RateLimit` if none is configured). Returns the
+/// result for each expense in the same order as the expenses.
+pub async fn apply_bulk_operation(
+    client: &Client,
+    expenses: &[Expense],
+    operation: &BulkOperation,
+) -> Vec<Result<(), anyhow::Error>> {
+    // The target group is the same for every expense, so fetch it once
+    let group = match operation {
+        BulkOperation::Move(group_id) if *group_id != 0 && !expenses.is_empty() => {
+            match client.groups().get_group(*group_id).await {
+                Ok(group) => Some(group),
+                Err(e) => {
+                    let message = e.to_string();
+                    return expenses
+                        .iter()
+                        .map(|_| Err(anyhow::anyhow!("{}", message)))
+                        .collect();
+                }
+            }
+        }
+        _ => None,
+    };
+
+    client
+        .execute_bulk(expenses.to_vec(), |client, expense| {
+            let operation = operation.clone();
+            let request = operation.update_request();
+            let group = group.clone();
+            async move {
+                let id = expense.id.unwrap_or_default();
+                match operation {
+                    BulkOperation::Delete => client.expenses().delete_expense(id).await,
+                    BulkOperation::Restore => client.expenses().restore_expense(id).await,
+                    // Members of the expense must already be in the group
+                    BulkOperation::Move(group_id) => client
+                        .expenses()
+                        .move_to_group(&expense, group_id, group.as_ref(), false)
+                        .await
+                        .map(|_| ()),
+                    _ => client
+                        .expenses()
+                        .update_expense(id, request)
+                        .await
+                        .map(|_| ()),
+                }
+            }
+        })
+        .await
+}
+
+/// Parses an amount such as `12.50`.
+fn amount(value: Option<&str>) -> Option<Decimal> {
+    value.and_then(|v| Decimal::from_str(v.trim()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn filter_matches_description_payer_and_cost() {
+        let expense: Expense = serde_json::from_value(json!({
+            "id": 1,
+            "group_id": 7,
+            "date": "2024-03-01T00:00:00Z",
+            "description": "Groceries at Tesco",
+            "cost": "42.50",
+            "users": [
+                {"user_id": 10, "paid_share": "42.50", "owed_share": "21.25"},
+                {"user_id": 11, "paid_share": "0.00", "owed_share": "21.25"}
+            ]
+        }))
+        .unwrap();
+
+        let filter = ExpenseFilter {
+            group_id: Some(7),
+            description: Some(Regex::new("(?i)grocer").unwrap()),
+            paid_by: Some(10),
+            min_cost: Some(Decimal::new(4000, 2)),
+            max_cost: Some(Decimal::new(4250, 2)),
+            ..ExpenseFilter::default()
+        };
+        assert!(filter.matches(&expense));
+        assert!(!filter.is_empty());
+        assert!(ExpenseFilter::default().is_empty());
+        assert!(!ExpenseFilter {
+            paid_by: Some(11),
+            ..filter.clone()
+        }
+        .matches(&expense));
+        assert!(!ExpenseFilter {
+            max_cost: Some(Decimal::new(4249, 2)),
+            ..filter.clone()
+        }
+        .matches(&expense));
+        assert!(!ExpenseFilter {
+            dated_after: Some(Utc.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()),
+            ..filter
+        }
+        .matches(&expense));
+
+        assert!(!BulkOperation::Restore.applies_to(&expense));
+        assert!(BulkOperation::Delete.applies_to(&expense));
+    }
+}
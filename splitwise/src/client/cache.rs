@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Response body stored in a cache, along with when it was stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// When the response was received.
+    pub stored_at: DateTime<Utc>,
+
+    /// Raw JSON response body.
+    pub body: String,
+}
+
+/// Storage for cached responses. Keys are safe to use as file names.
+pub trait CacheBackend: Debug + Send + Sync {
+    /// Returns the entry stored under the key, if any.
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    /// Stores an entry under the key, replacing any existing one.
+    fn put(&self, key: &str, entry: CacheEntry);
+
+    /// Removes the entry stored under the key, if any.
+    fn remove(&self, key: &str);
+}
+
+/// Cache backend that keeps entries in memory, shared by clones of a client.
+#[derive(Debug, Default)]
+pub struct MemoryCacheBackend {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl CacheBackend for MemoryCacheBackend {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+/// Cache backend that keeps each entry in a JSON file in a directory, so that
+/// entries are shared between processes. Unreadable entries are treated as
+/// missing, and write failures are ignored.
+#[derive(Debug)]
+pub struct DiskCacheBackend {
+    dir: PathBuf,
+}
+
+impl DiskCacheBackend {
+    /// Creates a disk cache in the given directory, creating it if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+}
+
+impl CacheBackend for DiskCacheBackend {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let contents = std::fs::read(self.path(key)).ok()?;
+        serde_json::from_slice(&contents).ok()
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) {
+        // Write to a temporary file first so readers never see partial entries
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let written = serde_json::to_vec(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(write_private(&tmp, &contents)?))
+            .and_then(|_| Ok(std::fs::rename(&tmp, &path)?));
+        if written.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = std::fs::remove_file(self.path(key));
+    }
+}
+
+/// Writes a file only the current user can read, since cached responses
+/// include personal details.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)?.write_all(contents)
+}
+
+/// Cache for responses from read endpoints that rarely change.
+///
+/// Each cacheable endpoint has its own time-to-live. By default these are:
+/// - `get_currencies` and `get_categories`: 1 day
+/// - `get_current_user`: 1 hour
+/// - `get_groups` and `get_friends`: 5 minutes
+///
+/// Entries are invalidated when a mutation that affects them succeeds, eg
+/// `create_group` invalidates `get_groups`.
+#[derive(Debug, Clone)]
+pub struct Cache {
+    backend: Arc<dyn CacheBackend>,
+    ttls: HashMap<String, Duration>,
+}
+
+impl Cache {
+    /// Creates a cache with the given backend and the default TTLs.
+    pub fn new(backend: Arc<dyn CacheBackend>) -> Self {
+        let ttls = [
+            ("get_currencies", Duration::from_secs(24 * 60 * 60)),
+            ("get_categories", Duration::from_secs(24 * 60 * 60)),
+            ("get_current_user", Duration::from_secs(60 * 60)),
+            ("get_groups", Duration::from_secs(5 * 60)),
+            ("get_friends", Duration::from_secs(5 * 60)),
+        ]
+        .iter()
+        .map(|(endpoint, ttl)| (endpoint.to_string(), *ttl))
+        .collect();
+        Self { backend, ttls }
+    }
+
+    /// Creates a cache that keeps entries in memory.
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryCacheBackend::default()))
+    }
+
+    /// Creates a cache that keeps entries in files in the given directory.
+    /// `get_current_user` is not cached, since it holds the user's email,
+    /// unless enabled again with `with_ttl`.
+    pub fn on_disk(dir: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        Ok(Self::new(Arc::new(DiskCacheBackend::new(dir)?))
+            .with_ttl("get_current_user", Duration::ZERO))
+    }
+
+    /// Overrides the time-to-live of an endpoint, eg `get_groups`. A zero TTL
+    /// disables caching of that endpoint.
+    pub fn with_ttl(mut self, endpoint: &str, ttl: Duration) -> Self {
+        self.ttls.insert(endpoint.to_string(), ttl);
+        self
+    }
+
+    /// Removes the cached response of an endpoint.
+    pub(crate) fn invalidate(&self, namespace: &str, endpoint: &str) {
+        self.backend.remove(&key(namespace, endpoint));
+    }
+
+    /// Whether responses from the endpoint are cached.
+    pub(crate) fn is_cached(&self, endpoint: &str) -> bool {
+        self.ttl(endpoint).is_some()
+    }
+
+    /// Returns the cached response body of an endpoint, if it has not expired.
+    pub(crate) fn get(&self, namespace: &str, endpoint: &str) -> Option<String> {
+        let ttl = self.ttl(endpoint)?;
+        let entry = self.backend.get(&key(namespace, endpoint))?;
+        let age = Utc::now()
+            .signed_duration_since(entry.stored_at)
+            .to_std()
+            .ok()?;
+        if age > ttl {
+            return None;
+        }
+        Some(entry.body)
+    }
+
+    /// Stores the response body of an endpoint.
+    pub(crate) fn put(&self, namespace: &str, endpoint: &str, body: String) {
+        let entry = CacheEntry {
+            stored_at: Utc::now(),
+            body,
+        };
+        self.backend.put(&key(namespace, endpoint), entry);
+    }
+
+    /// Removes the cached responses made stale by a successful mutation.
+    pub(crate) fn invalidate_after(&self, namespace: &str, mutation: &str) {
+        for endpoint in invalidated_by(mutation) {
+            self.invalidate(namespace, endpoint);
+        }
+    }
+
+    fn ttl(&self, endpoint: &str) -> Option<Duration> {
+        self.ttls
+            .get(endpoint)
+            .copied()
+            .filter(|ttl| !ttl.is_zero())
+    }
+}
+
+fn key(namespace: &str, endpoint: &str) -> String {
+    format!("{}-{}", namespace, endpoint)
+}
+
+/// Cached endpoints whose responses may change after the given mutation.
+fn invalidated_by(mutation: &str) -> &'static [&'static str] {
+    match mutation {
+        // Group and friend listings include balances, which change with
+        // expenses and group membership
+        "create_expense" | "update_expense" | "delete_expense" | "undelete_expense"
+        | "parse_sentence" => &["get_groups", "get_friends"],
+        "create_group"
+        | "delete_group"
+        | "undelete_group"
+        | "add_user_to_group"
+        | "remove_user_from_group" => &["get_groups", "get_friends"],
+        "create_friends" | "delete_friend" => &["get_friends"],
+        "update_user" => &["get_current_user"],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_expires_entries() {
+        let cache = Cache::in_memory().with_ttl("get_groups", Duration::from_secs(60));
+        cache.put("ns", "get_groups", "{}".to_string());
+        assert_eq!(cache.get("ns", "get_groups"), Some("{}".to_string()));
+        assert_eq!(cache.get("other", "get_groups"), None);
+
+        cache.backend.put(
+            &key("ns", "get_groups"),
+            CacheEntry {
+                stored_at: Utc::now() - chrono::Duration::seconds(61),
+                body: "{}".to_string(),
+            },
+        );
+        assert_eq!(cache.get("ns", "get_groups"), None);
+    }
+
+    #[test]
+    fn cache_invalidates_after_mutation() {
+        let cache = Cache::in_memory();
+        cache.put("ns", "get_groups", "{}".to_string());
+        cache.put("ns", "get_currencies", "{}".to_string());
+
+        cache.invalidate_after("ns", "create_group");
+        assert_eq!(cache.get("ns", "get_groups"), None);
+        assert!(cache.get("ns", "get_currencies").is_some());
+    }
+
+    #[test]
+    fn disk_cache_is_shared_between_instances() {
+        let dir = std::env::temp_dir().join(format!("splitwise-cache-{}", std::process::id()));
+        let first = Cache::on_disk(&dir).unwrap();
+        first.put("ns", "get_categories", "{\"categories\":[]}".to_string());
+
+        let second = Cache::on_disk(&dir).unwrap();
+        assert_eq!(
+            second.get("ns", "get_categories"),
+            Some("{\"categories\":[]}".to_string())
+        );
+        second.invalidate("ns", "get_categories");
+        assert_eq!(first.get("ns", "get_categories"), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,10 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use reqwest::header;
 use reqwest::StatusCode;
@@ -7,14 +12,29 @@ use secrecy::ExposeSecret;
 use secrecy::Secret;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use tokio::task::JoinSet;
+use tracing::debug;
+use tracing::field;
+use tracing::info_span;
+use tracing::warn;
+use tracing::Instrument;
+use tracing::Span;
 use url::Url;
 
+use crate::client::cache::Cache;
 use crate::client::comments::CommentsSvc;
+use crate::client::dry_run::DryRunResponse;
+use crate::client::dry_run::Plan;
+use crate::client::dry_run::PlannedRequest;
 use crate::client::expenses::ExpensesSvc;
 use crate::client::friends::FriendsSvc;
 use crate::client::groups::GroupsSvc;
 use crate::client::notifications::NotificationsSvc;
 use crate::client::other::OtherSvc;
+use crate::client::rate_limit::RateLimit;
+use crate::client::rate_limit::RateLimiter;
+use crate::client::redact::redact_body;
+use crate::client::redact::redact_headers;
 use crate::client::users::UsersSvc;
 use crate::model::shared::ErrorForbiddenOrNotFound;
 use crate::model::shared::ErrorUnauthorized;
@@ -24,35 +44,57 @@ use crate::model::shared::ErrorUnauthorized;
 pub struct Client {
     http_client: reqwest::Client,
     pub(crate) base_url: Url,
-    authorization: Secret<String>,
+    authorization: Option<Secret<String>>,
+    locale: Option<String>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    plan: Option<Plan>,
+    log_bodies: bool,
+    cache: Option<Cache>,
 }
 
 impl Default for Client {
     /// Creates a default Splitwise API client using a default Reqwest HTTP
     /// client, the official Splitwise API URL, and an API key sourced from
-    /// the environment variable `SPLITWISE_API_KEY`.
+    /// the environment variable `SPLITWISE_API_KEY`. Requests fail if the
+    /// variable is unset or empty and no credential is set otherwise; use
+    /// `from_env` to fail up front instead.
     fn default() -> Self {
         let http_client = reqwest::Client::default();
         let base_url = Url::parse("https://secure.splitwise.com/api/v3.0/").unwrap();
-        let api_key: String =
-            std::env::var("SPLITWISE_API_KEY").unwrap_or_else(|_| String::from(""));
-        let authorization = format!("Bearer {}", api_key).into();
+        let authorization = std::env::var("SPLITWISE_API_KEY")
+            .ok()
+            .filter(|key| !key.is_empty())
+            .map(|key| format!("Bearer {}", key).into());
         Self {
             http_client,
             base_url,
             authorization,
+            locale: None,
+            rate_limiter: None,
+            plan: None,
+            log_bodies: false,
+            cache: None,
         }
     }
 }
 
 impl Client {
+    /// Creates a default Splitwise API client, failing if the environment
+    /// variable `SPLITWISE_API_KEY` holds no API key.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let client = Self::default();
+        if !client.has_credential() {
+            bail!("no Splitwise credential configured: SPLITWISE_API_KEY is unset or empty")
+        }
+        Ok(client)
+    }
+
     /// Builds a new Splitwise client from the current one, with the given HTTP
     /// client as an override.
     pub fn with_http_client(self, http_client: reqwest::Client) -> Self {
         Self {
             http_client,
-            base_url: self.base_url,
-            authorization: self.authorization,
+            ..self
         }
     }
 
@@ -64,156 +106,456 @@ impl Client {
             ensured_base_url.push('/');
         }
         let base_url = Url::parse(&ensured_base_url)?;
-        Ok(Self {
-            http_client: self.http_client,
-            base_url,
-            authorization: self.authorization,
-        })
+        Ok(Self { base_url, ..self })
     }
 
     /// Builds a new Splitwise client from the current one, with the given API
     /// key as an override.
     pub fn with_api_key(self, api_key: Secret<String>) -> Self {
-        let authorization = format!("Bearer {}", api_key.expose_secret()).into();
+        let authorization = Some(format!("Bearer {}", api_key.expose_secret()).into());
         Self {
-            http_client: self.http_client,
-            base_url: self.base_url,
             authorization,
+            ..self
         }
     }
 
-    /// Builds a new Splitwise client from the current one, performing an OAuth
-    /// 2.0 Authorization Code flow.
+    /// Builds a new Splitwise client from the current one, with the given
+    /// OAuth 2.0 access token as an override.
+    pub fn with_oauth_token(self, access_token: Secret<String>) -> Self {
+        // Access tokens are bearer tokens, sent just like API keys
+        self.with_api_key(access_token)
+    }
+
+    // TODO: Builds a new Splitwise client from the current one, performing an
+    // OAuth 2.0 Authorization Code flow.
     // pub fn with_oauth(self, )
 
+    /// Builds a new Splitwise client from the current one, asking for
+    /// messages and names in the given locale, eg `en` or `pt-BR`.
+    pub fn with_locale(self, locale: &str) -> Self {
+        Self {
+            locale: Some(locale.to_string()),
+            ..self
+        }
+    }
+
+    /// Whether an API key or OAuth token is configured.
+    pub fn has_credential(&self) -> bool {
+        self.authorization.is_some()
+    }
+
+    /// Builds a new Splitwise client from the current one, throttling all
+    /// requests according to the given rate limit. The limit is shared by
+    /// clones of the returned client.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not finite and positive.
+    pub fn with_rate_limit(self, rate_limit: RateLimit) -> Self {
+        Self {
+            rate_limiter: Some(Arc::new(RateLimiter::new(rate_limit))),
+            ..self
+        }
+    }
+
+    /// Builds a new Splitwise client from the current one that logs request
+    /// and response bodies at debug level. Authorization headers, emails and
+    /// passwords are redacted.
+    pub fn with_body_logging(self) -> Self {
+        Self {
+            log_bodies: true,
+            ..self
+        }
+    }
+
+    /// Builds a new Splitwise client from the current one, caching responses
+    /// from read endpoints that rarely change. The cache is shared by clones
+    /// of the returned client, and by other clients using the same backend.
+    pub fn with_cache(self, cache: Cache) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Removes the cached response of an endpoint, eg `get_groups`, if a cache
+    /// is configured.
+    pub fn invalidate_cache(&self, endpoint: &str) {
+        if let Some(ref cache) = self.cache {
+            cache.invalidate(&self.cache_namespace(), endpoint);
+        }
+    }
+
+    /// Identifies the account and API this client talks to, so that clients
+    /// sharing a cache backend never see each other's responses. The hash is
+    /// stable across builds, so that on-disk entries stay valid.
+    fn cache_namespace(&self) -> String {
+        let authorization = self
+            .authorization
+            .as_ref()
+            .map(|a| a.expose_secret().as_str())
+            .unwrap_or_default();
+        let identity = [
+            self.base_url.as_str(),
+            authorization,
+            self.locale.as_deref().unwrap_or_default(),
+        ]
+        .join("\0");
+        format!("{:016x}", fnv1a(identity.as_bytes()))
+    }
+
+    /// Builds a new Splitwise client from the current one in dry-run mode.
+    /// Mutating calls are not sent to Splitwise, but recorded into a `Plan`
+    /// that can be inspected with `plan`, and return a placeholder response
+    /// indicating success. Read-only calls are still sent.
+    ///
+    /// Created or updated expenses and groups are echoed from the request, with
+    /// a placeholder ID of 0, so that callers can carry on as if Splitwise had
+    /// accepted them.
+    pub fn with_dry_run(self) -> Self {
+        Self {
+            plan: Some(Plan::default()),
+            ..self
+        }
+    }
+
+    /// Requests recorded in dry-run mode, or `None` if the client is not in
+    /// dry-run mode.
+    pub fn plan(&self) -> Option<&Plan> {
+        self.plan.as_ref()
+    }
+
+    /// Returns a client that is guaranteed to be rate limited, using the
+    /// default `RateLimit` if this client has none configured.
+    pub(crate) fn rate_limited(&self) -> Self {
+        match self.rate_limiter {
+            Some(_) => self.clone(),
+            None => self.clone().with_rate_limit(RateLimit::default()),
+        }
+    }
+
+    /// Runs an operation for each item concurrently, within this client's rate
+    /// limit, returning the results in the same order as the items.
+    pub(crate) async fn execute_bulk<I, T, F, Fut>(
+        &self,
+        items: Vec<I>,
+        operation: F,
+    ) -> Vec<Result<T, anyhow::Error>>
+    where
+        F: Fn(Client, I) -> Fut,
+        Fut: Future<Output = Result<T, anyhow::Error>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let client = self.rate_limited();
+        let mut tasks = JoinSet::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let task = operation(client.clone(), item);
+            tasks.spawn(async move { (i, task.await) });
+        }
+
+        let mut results: Vec<Option<Result<T, anyhow::Error>>> =
+            (0..tasks.len()).map(|_| None).collect();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((i, result)) => results[i] = Some(result),
+                Err(e) => {
+                    if e.is_panic() {
+                        std::panic::resume_unwind(e.into_panic());
+                    }
+                }
+            }
+        }
+        results
+            .into_iter()
+            .map(|r| r.unwrap_or_else(|| Err(anyhow!("bulk operation did not complete"))))
+            .collect()
+    }
+
     /// Decodes HTTP response into Splitwise API types or errors.
-    pub(crate) async fn process_response<T>(
+    pub(crate) fn process_response<T>(
         &self,
-        response: reqwest::Response,
+        status: StatusCode,
+        body: &[u8],
     ) -> Result<T, anyhow::Error>
     where
         T: DeserializeOwned,
     {
-        match response.status() {
+        match status {
             StatusCode::OK => {
-                let decoded = response.json::<T>().await?;
+                let decoded = serde_json::from_slice::<T>(body)?;
                 Ok(decoded)
             }
             StatusCode::UNAUTHORIZED => {
-                let decoded = response.json::<ErrorUnauthorized>().await?;
+                let decoded = serde_json::from_slice::<ErrorUnauthorized>(body)?;
                 bail!(decoded.error)
             }
             StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => {
-                let decoded = response.json::<ErrorForbiddenOrNotFound>().await?;
+                let decoded = serde_json::from_slice::<ErrorForbiddenOrNotFound>(body)?;
                 bail!(decoded.errors.base.join("; "))
             }
-            _ => bail!("unexpected HTTP status code: {}", response.status()),
+            _ => bail!("unexpected HTTP status code: {}", status),
+        }
+    }
+
+    /// Sends an HTTP request wrapped with auth, honoring the rate limit if one
+    /// is configured. Each request is traced in a span recording its service,
+    /// endpoint, status, latency and retry count.
+    async fn send<T>(&self, request: reqwest::RequestBuilder) -> Result<T, anyhow::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let authorization = match self.authorization {
+            Some(ref authorization) => authorization.expose_secret(),
+            None => bail!(
+                "no Splitwise credential configured: set SPLITWISE_API_KEY or use `with_api_key`"
+            ),
+        };
+        let mut request = request.header(header::AUTHORIZATION, authorization);
+        if let Some(ref locale) = self.locale {
+            request = request.header(header::ACCEPT_LANGUAGE, locale);
+        }
+        let request = request.build()?;
+        let endpoint = endpoint_name(&self.base_url, request.url());
+        let span = info_span!(
+            "splitwise_request",
+            service = service_name(&endpoint),
+            endpoint = %endpoint,
+            method = %request.method(),
+            status = field::Empty,
+            latency_ms = field::Empty,
+            retries = field::Empty,
+        );
+
+        let result = async {
+            if self.log_bodies {
+                let body = request
+                    .body()
+                    .and_then(|b| b.as_bytes())
+                    .unwrap_or_default();
+                debug!(
+                    headers = %redact_headers(request.headers()),
+                    body = %redact_body(body),
+                    "sending request"
+                );
+            }
+
+            let start = Instant::now();
+            let (response, retries) = self.execute(request).await?;
+            let status = response.status();
+            let body = response.bytes().await?;
+
+            let span = Span::current();
+            span.record("status", status.as_u16());
+            span.record("latency_ms", start.elapsed().as_millis() as u64);
+            span.record("retries", retries);
+            if self.log_bodies {
+                debug!(body = %redact_body(&body), "received response");
+            }
+
+            self.process_response(status, &body)
         }
+        .instrument(span.clone())
+        .await;
+
+        if let Err(ref e) = result {
+            span.in_scope(|| warn!(error = %e, "request failed"));
+        }
+        result
     }
 
-    /// Perform an HTTP GET wrapped with auth.
+    /// Executes an HTTP request, honoring the rate limit if one is configured
+    /// and retrying requests rejected with `429 Too Many Requests`. Returns the
+    /// final response along with the number of retries it took.
+    async fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<(reqwest::Response, u32), anyhow::Error> {
+        let limiter = match self.rate_limiter {
+            Some(ref limiter) => limiter,
+            None => {
+                let response = self.http_client.execute(request).await?;
+                return Ok((response, 0));
+            }
+        };
+
+        let mut retries = 0;
+        loop {
+            // Request bodies are always buffered, so they can be cloned
+            let attempt = request.try_clone().unwrap();
+            let permit = limiter.acquire().await;
+            let response = self.http_client.execute(attempt).await?;
+            drop(permit);
+
+            if response.status() != StatusCode::TOO_MANY_REQUESTS
+                || retries >= limiter.limit.max_retries
+            {
+                return Ok((response, retries));
+            }
+
+            let delay = response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or_else(|| limiter.backoff(retries));
+            debug!(
+                delay_ms = delay.as_millis() as u64,
+                "rate limited, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+
+    /// Sends an HTTP request that modifies data in Splitwise, invalidating any
+    /// cached responses it makes stale. In dry-run mode, the request is
+    /// recorded into the plan instead.
+    async fn mutate<T>(&self, request: reqwest::RequestBuilder) -> Result<T, anyhow::Error>
+    where
+        T: DeserializeOwned + DryRunResponse,
+    {
+        let plan = match self.plan {
+            Some(ref plan) => plan,
+            None => {
+                let (client, request) = request.build_split();
+                let request = request?;
+                let endpoint = endpoint_name(&self.base_url, request.url());
+                let response = self
+                    .send(reqwest::RequestBuilder::from_parts(client, request))
+                    .await?;
+                if let Some(ref cache) = self.cache {
+                    cache.invalidate_after(&self.cache_namespace(), &endpoint);
+                }
+                return Ok(response);
+            }
+        };
+
+        let request = request.build()?;
+        let path = self
+            .base_url
+            .make_relative(request.url())
+            .unwrap_or_else(|| request.url().to_string());
+        let bytes = request.body().and_then(|b| b.as_bytes());
+        let body = bytes.map(|b| String::from_utf8_lossy(b).into_owned());
+        debug!(method = %request.method(), path = %path, "recorded request in dry-run plan");
+        plan.record(PlannedRequest {
+            method: request.method().to_string(),
+            path,
+            body,
+        });
+        Ok(T::dry_run(bytes))
+    }
+
+    /// Perform an HTTP GET wrapped with auth, served from the cache if one is
+    /// configured and the endpoint is cacheable.
     pub(crate) async fn get<T>(&self, url: Url) -> Result<T, anyhow::Error>
     where
         T: DeserializeOwned,
     {
-        let response = self
-            .http_client
-            .get(url)
-            .header(header::AUTHORIZATION, self.authorization.expose_secret())
-            .send()
-            .await?;
-        let payload = self.process_response(response).await?;
-        Ok(payload)
+        let endpoint = endpoint_name(&self.base_url, &url);
+        let cache = match self.cache {
+            Some(ref cache) if cache.is_cached(&endpoint) => cache,
+            _ => return self.send(self.http_client.get(url)).await,
+        };
+
+        let namespace = self.cache_namespace();
+        if let Some(body) = cache.get(&namespace, &endpoint) {
+            if let Ok(decoded) = serde_json::from_str::<T>(&body) {
+                debug!(endpoint = %endpoint, "serving response from cache");
+                return Ok(decoded);
+            }
+        }
+
+        let response: serde_json::Value = self.send(self.http_client.get(url)).await?;
+        cache.put(&namespace, &endpoint, response.to_string());
+        Ok(serde_json::from_value(response)?)
     }
 
     // TODO: Can body be consumed rather than be a reference?
     /// Perform an HTTP POST wrapped with auth.
     pub(crate) async fn post<T, S>(&self, url: Url, body: &S) -> Result<T, anyhow::Error>
+    where
+        T: DeserializeOwned + DryRunResponse,
+        S: Serialize + ?Sized,
+    {
+        self.mutate(self.http_client.post(url).json(body)).await
+    }
+
+    /// Perform an HTTP POST wrapped with auth, for endpoints that do not modify
+    /// data. These are sent even in dry-run mode.
+    pub(crate) async fn post_read_only<T, S>(&self, url: Url, body: &S) -> Result<T, anyhow::Error>
     where
         T: DeserializeOwned,
         S: Serialize + ?Sized,
     {
-        let response = self
-            .http_client
-            .post(url)
-            .header(header::AUTHORIZATION, self.authorization.expose_secret())
-            .json(body)
-            .send()
-            .await?;
-        let payload = self.process_response(response).await?;
-        Ok(payload)
+        self.send(self.http_client.post(url).json(body)).await
     }
 
     // TODO: Merge this with post
-    /// Perform an HTTP POST wrapped with auth, with no request body.
+    /// Perform an HTTP POST wrapped with auth, with a form-encoded request
+    /// body.
     pub(crate) async fn post_form<T, S>(&self, url: Url, body: &S) -> Result<T, anyhow::Error>
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + DryRunResponse,
         S: Serialize + ?Sized,
     {
-        let response = self
-            .http_client
-            .post(url)
-            .header(header::AUTHORIZATION, self.authorization.expose_secret())
-            .form(body)
-            .send()
-            .await?;
-        let payload = self.process_response(response).await?;
-        Ok(payload)
+        self.mutate(self.http_client.post(url).form(body)).await
     }
 
     // TODO: Merge this with post
     /// Perform an HTTP POST wrapped with auth, with no request body.
     pub(crate) async fn post_no_body<T>(&self, url: Url) -> Result<T, anyhow::Error>
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + DryRunResponse,
     {
-        let response = self
-            .http_client
-            .post(url)
-            .header(header::AUTHORIZATION, self.authorization.expose_secret())
-            .send()
-            .await?;
-        let payload = self.process_response(response).await?;
-        Ok(payload)
+        self.mutate(self.http_client.post(url)).await
     }
 
     /// Users API group.
-    pub fn users(&self) -> UsersSvc {
+    pub fn users(&self) -> UsersSvc<'_> {
         UsersSvc::new(self)
     }
 
     /// Groups API group.
-    pub fn groups(&self) -> GroupsSvc {
+    pub fn groups(&self) -> GroupsSvc<'_> {
         GroupsSvc::new(self)
     }
 
     /// Friends API group.
-    pub fn friends(&self) -> FriendsSvc {
+    pub fn friends(&self) -> FriendsSvc<'_> {
         FriendsSvc::new(self)
     }
 
     /// Expenses API group.
-    pub fn expenses(&self) -> ExpensesSvc {
+    pub fn expenses(&self) -> ExpensesSvc<'_> {
         ExpensesSvc::new(self)
     }
 
     /// Comments API group.
-    pub fn comments(&self) -> CommentsSvc {
+    pub fn comments(&self) -> CommentsSvc<'_> {
         CommentsSvc::new(self)
     }
 
     /// Notifications API group.
-    pub fn notifications(&self) -> NotificationsSvc {
+    pub fn notifications(&self) -> NotificationsSvc<'_> {
         NotificationsSvc::new(self)
     }
 
     /// Other API group.
-    pub fn other(&self) -> OtherSvc {
+    pub fn other(&self) -> OtherSvc<'_> {
         OtherSvc::new(self)
     }
 }
 
+/// 64-bit FNV-1a hash. Unlike `DefaultHasher`, it stays the same across Rust
+/// versions, so it can be used for IDs and file names that must be stable.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
 pub(crate) fn join_errors(errors: &HashMap<String, Vec<String>>) -> String {
     let mut error_text = String::from("");
     for (k, v) in errors {
@@ -221,3 +563,81 @@ pub(crate) fn join_errors(errors: &HashMap<String, Vec<String>>) -> String {
     }
     error_text
 }
+
+/// Name of the API endpoint a URL points to, eg `get_expense` for
+/// `get_expense/123`.
+fn endpoint_name(base_url: &Url, url: &Url) -> String {
+    let relative = base_url
+        .make_relative(url)
+        .unwrap_or_else(|| url.path().to_string());
+    let path = relative.split('?').next().unwrap_or_default();
+    path.split('/').next().unwrap_or_default().to_string()
+}
+
+/// Name of the API group an endpoint belongs to, matching the service that
+/// calls it.
+fn service_name(endpoint: &str) -> &'static str {
+    // Checked in order, since eg `add_user_to_group` is a groups endpoint
+    let services = [
+        ("expense", "expenses"),
+        ("comment", "comments"),
+        ("notification", "notifications"),
+        ("friend", "friends"),
+        ("group", "groups"),
+        ("user", "users"),
+    ];
+    services
+        .iter()
+        .find(|(keyword, _)| endpoint.contains(keyword))
+        .map(|(_, service)| *service)
+        .unwrap_or("other")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn endpoint_and_service_names_work() {
+        let base_url = Url::parse("https://secure.splitwise.com/api/v3.0/").unwrap();
+        let cases = [
+            ("get_expense/123", "get_expense", "expenses"),
+            ("get_comments?expense_id=1", "get_comments", "comments"),
+            ("add_user_to_group", "add_user_to_group", "groups"),
+            ("get_current_user", "get_current_user", "users"),
+            ("create_friends", "create_friends", "friends"),
+            ("get_currencies", "get_currencies", "other"),
+        ];
+        for (path, endpoint, service) in cases {
+            let url = base_url.join(path).unwrap();
+            assert_eq!(endpoint_name(&base_url, &url), endpoint);
+            assert_eq!(service_name(endpoint), service);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_is_served_from_cache() {
+        let cache = Cache::in_memory();
+        let client = Client::default()
+            .with_base_url("http://localhost:1/api/v3.0")
+            .unwrap()
+            .with_cache(cache.clone());
+        cache.put(
+            &client.cache_namespace(),
+            "get_currencies",
+            r#"{"currencies":[{"currency_code":"USD","unit":"$"}]}"#.to_string(),
+        );
+
+        let currencies = client.other().get_currencies().await.unwrap();
+        assert_eq!(currencies[0].currency_code.as_deref(), Some("USD"));
+
+        client.invalidate_cache("get_currencies");
+        assert!(client.other().get_currencies().await.is_err());
+    }
+}
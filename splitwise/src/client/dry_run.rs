@@ -0,0 +1,260 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::client::redact::redact_body;
+use crate::model::comments::Comment;
+use crate::model::comments::CommentWrapper;
+use crate::model::expenses::ExpensesWrapper;
+use crate::model::friends::AddFriendsResponse;
+use crate::model::friends::DeleteFriendResponse;
+use crate::model::groups::Group;
+use crate::model::groups::GroupAddUserResponse;
+use crate::model::groups::GroupDeleteResponse;
+use crate::model::groups::GroupRemoveUserResponse;
+use crate::model::groups::GroupRestoreResponse;
+use crate::model::groups::GroupWrapper;
+use crate::model::other::ParseSentenceResponse;
+use crate::model::shared::Success;
+use crate::model::users::UserWrapper;
+
+/// Request that a client in dry-run mode would have sent to Splitwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedRequest {
+    /// HTTP method, eg `POST`.
+    pub method: String,
+
+    /// Path and query of the request, relative to the API base URL.
+    pub path: String,
+
+    /// Request body exactly as it would have been encoded, if any.
+    pub body: Option<String>,
+}
+
+impl PlannedRequest {
+    /// Request body with sensitive values such as emails and passwords
+    /// redacted, for printing or logging.
+    pub fn redacted_body(&self) -> Option<String> {
+        self.body.as_ref().map(|b| redact_body(b.as_bytes()))
+    }
+}
+
+/// Requests recorded by a client in dry-run mode, in the order they were
+/// made. Clones share the same underlying list.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    requests: Arc<Mutex<Vec<PlannedRequest>>>,
+}
+
+impl Plan {
+    /// Returns a copy of the requests recorded so far.
+    pub fn requests(&self) -> Vec<PlannedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Whether no requests have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.requests.lock().unwrap().is_empty()
+    }
+
+    /// Removes all recorded requests.
+    pub fn clear(&self) {
+        self.requests.lock().unwrap().clear()
+    }
+
+    pub(crate) fn record(&self, request: PlannedRequest) {
+        self.requests.lock().unwrap().push(request)
+    }
+}
+
+/// Placeholder returned by a mutating call in dry-run mode, in place of a
+/// response from Splitwise. Placeholders always indicate success, and created
+/// or updated objects echo the request.
+pub(crate) trait DryRunResponse {
+    /// Placeholder for a request with this body.
+    fn dry_run(body: Option<&[u8]>) -> Self;
+}
+
+/// Object echoing the fields of a JSON request body, with a placeholder ID of
+/// 0, or `None` if the body does not describe one.
+fn echo<T: DeserializeOwned>(body: Option<&[u8]>) -> Option<T> {
+    let mut value: Value = serde_json::from_slice(body?).ok()?;
+    value
+        .as_object_mut()?
+        .insert("id".to_string(), Value::from(0));
+    serde_json::from_value(value).ok()
+}
+
+impl DryRunResponse for ExpensesWrapper {
+    fn dry_run(body: Option<&[u8]>) -> Self {
+        Self {
+            expenses: echo(body).into_iter().collect(),
+            errors: None,
+        }
+    }
+}
+
+impl DryRunResponse for Success {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            success: true,
+            errors: None,
+        }
+    }
+}
+
+impl DryRunResponse for GroupWrapper {
+    fn dry_run(body: Option<&[u8]>) -> Self {
+        Self {
+            group: Group {
+                id: Some(0),
+                ..echo(body).unwrap_or_default()
+            },
+        }
+    }
+}
+
+impl DryRunResponse for GroupDeleteResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self { success: true }
+    }
+}
+
+impl DryRunResponse for GroupRestoreResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            success: true,
+            errors: None,
+        }
+    }
+}
+
+impl DryRunResponse for GroupAddUserResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            success: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl DryRunResponse for GroupRemoveUserResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            success: true,
+            errors: None,
+        }
+    }
+}
+
+impl DryRunResponse for AddFriendsResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self::default()
+    }
+}
+
+impl DryRunResponse for DeleteFriendResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            success: true,
+            errors: None,
+        }
+    }
+}
+
+impl DryRunResponse for CommentWrapper {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            comment: Comment {
+                id: 0,
+                content: String::new(),
+                comment_type: "User".to_string(),
+                relation_type: "ExpenseComment".to_string(),
+                relation_id: 0,
+                created_at: chrono::Utc::now(),
+                deleted_at: None,
+                user: None,
+            },
+        }
+    }
+}
+
+impl DryRunResponse for UserWrapper {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self::default()
+    }
+}
+
+impl DryRunResponse for ParseSentenceResponse {
+    fn dry_run(_body: Option<&[u8]>) -> Self {
+        Self {
+            expense: None,
+            valid: None,
+            confidence: None,
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::client::Client;
+    use crate::model::expenses::CreateExpenseRequest;
+    use crate::model::users::UpdateUserRequest;
+
+    #[tokio::test]
+    async fn dry_run_records_mutations_without_sending() {
+        let client = Client::default()
+            .with_base_url("http://localhost:1/api/v3.0")
+            .unwrap()
+            .with_dry_run();
+
+        client.expenses().delete_expense(123).await.unwrap();
+        let created = client
+            .expenses()
+            .create_expense(CreateExpenseRequest {
+                cost: "12.34".to_string(),
+                description: "Planned expense".to_string(),
+                ..CreateExpenseRequest::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].id, Some(0));
+        assert_eq!(created[0].cost.as_deref(), Some("12.34"));
+
+        let requests = client.plan().unwrap().requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "delete_expense/123");
+        assert_eq!(requests[0].body, None);
+        assert_eq!(requests[1].path, "create_expense");
+        assert!(requests[1]
+            .body
+            .as_ref()
+            .unwrap()
+            .contains("\"cost\":\"12.34\""));
+
+        client
+            .users()
+            .update_user(
+                1,
+                UpdateUserRequest {
+                    email: Some("me@example.com".to_string()),
+                    password: Some("hunter2".to_string()),
+                    ..UpdateUserRequest::default()
+                },
+            )
+            .await
+            .unwrap();
+        let request = &client.plan().unwrap().requests()[2];
+        assert!(request.body.as_ref().unwrap().contains("me@example.com"));
+        let redacted = request.redacted_body().unwrap();
+        assert!(!redacted.contains("me@example.com"));
+        assert!(!redacted.contains("hunter2"));
+    }
+}
@@ -2,14 +2,22 @@ use anyhow::bail;
 
 use crate::client::client::join_errors;
 use crate::client::client::Client;
+use crate::model::expenses::validate_email_reminder_days;
 use crate::model::expenses::CreateExpenseRequest;
 use crate::model::expenses::Expense;
 use crate::model::expenses::ExpenseWrapper;
 use crate::model::expenses::ExpensesWrapper;
 use crate::model::expenses::ListExpensesRequest;
+use crate::model::expenses::RepeatInterval;
 use crate::model::expenses::UpdateExpenseRequest;
+use crate::model::expenses::UserShare;
+use crate::model::groups::Group;
+use crate::model::groups::GroupUser;
 use crate::model::shared::Success;
 
+/// Number of expenses requested per page by `list_all_expenses`.
+const DEFAULT_PAGE_SIZE: i64 = 200;
+
 /// Expenses.
 ///
 /// [Splitwise API docs](https://dev.splitwise.com/#tag/expenses)
@@ -47,6 +55,36 @@ impl<'c> ExpensesSvc<'c> {
         Ok(response.expenses)
     }
 
+    /// List every one of the current user's expenses matching the request,
+    /// following pagination. The `limit` and `offset` of the request are used
+    /// as the page size and starting offset.
+    pub async fn list_all_expenses(
+        &self,
+        request: ListExpensesRequest,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        let limit = request
+            .limit
+            .filter(|l| *l > 0)
+            .unwrap_or(DEFAULT_PAGE_SIZE);
+        let mut offset = request.offset.unwrap_or(0);
+        let mut expenses = Vec::new();
+        loop {
+            let page = self
+                .list_expenses(ListExpensesRequest {
+                    limit: Some(limit),
+                    offset: Some(offset),
+                    ..request.clone()
+                })
+                .await?;
+            let len = page.len() as i64;
+            expenses.extend(page);
+            if len < limit {
+                return Ok(expenses);
+            }
+            offset += len;
+        }
+    }
+
     /// Creates an expense. You may either split an expense equally (only with
     /// `group_id` provided), or supply a list of shares.
     ///
@@ -69,11 +107,7 @@ impl<'c> ExpensesSvc<'c> {
 
         // User shares always take priority over equal split
         let mut request = request.clone();
-        if request.users.is_some() {
-            request.split_equally = false;
-        } else {
-            request.split_equally = true;
-        }
+        request.split_equally = request.users.is_none();
 
         let response: ExpensesWrapper = self.client.post(url, &request).await?;
 
@@ -86,6 +120,20 @@ impl<'c> ExpensesSvc<'c> {
         Ok(response.expenses)
     }
 
+    /// Creates many expenses concurrently, within the client's rate limit (or
+    /// the default `RateLimit` if none is configured). Returns the result of
+    /// each `create_expense` call in the same order as the requests.
+    pub async fn create_expenses(
+        &self,
+        requests: Vec<CreateExpenseRequest>,
+    ) -> Vec<Result<Vec<Expense>, anyhow::Error>> {
+        self.client
+            .execute_bulk(requests, |client, request| async move {
+                client.expenses().create_expense(request).await
+            })
+            .await
+    }
+
     /// Updates an expense. Parameters are the same as in `create_expense`, but
     /// you only need to include parameters that are changing from the previous
     /// values. If any values is supplied for `users`, all shares for the
@@ -137,6 +185,17 @@ impl<'c> ExpensesSvc<'c> {
         bail!("unknown error deleting expense: {}", id)
     }
 
+    /// Deletes many expenses concurrently, within the client's rate limit (or
+    /// the default `RateLimit` if none is configured). Returns the result of
+    /// each `delete_expense` call in the same order as the IDs.
+    pub async fn delete_expenses(&self, ids: Vec<i64>) -> Vec<Result<(), anyhow::Error>> {
+        self.client
+            .execute_bulk(ids, |client, id| async move {
+                client.expenses().delete_expense(id).await
+            })
+            .await
+    }
+
     /// Restore an expense.
     ///
     /// [Splitwise API docs](https://dev.splitwise.com/#tag/expenses/paths/~1undelete_expense~1{id}/post)
@@ -160,6 +219,206 @@ impl<'c> ExpensesSvc<'c> {
 
         bail!("unknown error undeleting expense")
     }
+
+    /// Lists the current user's recurring expenses across all groups and
+    /// friends, skipping deleted ones.
+    pub async fn list_recurring_expenses(&self) -> Result<Vec<Expense>, anyhow::Error> {
+        let expenses = self
+            .list_all_expenses(ListExpensesRequest::default())
+            .await?;
+        Ok(expenses
+            .into_iter()
+            .filter(|e| e.repeats == Some(true) && e.deleted_at.is_none())
+            .collect())
+    }
+
+    /// Changes the cadence at which an expense repeats. Setting
+    /// `RepeatInterval::Never` stops the recurrence.
+    pub async fn set_repeat_interval(
+        &self,
+        id: i64,
+        interval: RepeatInterval,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        let request = UpdateExpenseRequest {
+            repeat_interval: Some(interval.to_string()),
+            ..UpdateExpenseRequest::default()
+        };
+        self.update_expense(id, request).await
+    }
+
+    /// Stops a recurring expense from repeating. Past occurrences are kept.
+    pub async fn stop_recurrence(&self, id: i64) -> Result<Vec<Expense>, anyhow::Error> {
+        self.set_repeat_interval(id, RepeatInterval::Never).await
+    }
+
+    /// Reminds involved users the given number of days before the next
+    /// occurrence of a recurring expense, or turns reminders off with `None`.
+    /// The days must be one of `EMAIL_REMINDER_DAYS`.
+    pub async fn set_email_reminder(
+        &self,
+        id: i64,
+        days_in_advance: Option<i64>,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        let days_in_advance = days_in_advance
+            .map(validate_email_reminder_days)
+            .transpose()?;
+        let expense = self.get_expense(id).await?;
+        if expense.repeats != Some(true) {
+            bail!("expense {} does not recur", id)
+        }
+        let request = UpdateExpenseRequest {
+            email_reminder: Some(days_in_advance.is_some()),
+            email_reminder_in_advance: days_in_advance,
+            ..UpdateExpenseRequest::default()
+        };
+        self.update_expense(id, request).await
+    }
+
+    /// Moves an expense to another group, keeping what each user paid and
+    /// owes, or out of any group if `target_group_id` is 0. Fails if a user
+    /// involved in the expense is not a member of the target group, unless
+    /// `add_missing_members` is set, in which case they are added to it first.
+    pub async fn move_expense(
+        &self,
+        id: i64,
+        target_group_id: i64,
+        add_missing_members: bool,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        let expense = self.get_expense(id).await?;
+        let group = match target_group_id {
+            0 => None,
+            id => Some(self.client.groups().get_group(id).await?),
+        };
+        self.move_to_group(
+            &expense,
+            target_group_id,
+            group.as_ref(),
+            add_missing_members,
+        )
+        .await
+    }
+
+    /// Moves an expense to the group with the given ID, which is `group` if
+    /// it is a group rather than 0, as in `move_expense`.
+    pub(crate) async fn move_to_group(
+        &self,
+        expense: &Expense,
+        target_group_id: i64,
+        group: Option<&Group>,
+        add_missing_members: bool,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        let id = expense.id.unwrap_or_default();
+        // Expenses outside of groups have no members to check
+        let missing = group
+            .map(|group| missing_members(expense, group))
+            .unwrap_or_default();
+        if !missing.is_empty() && !add_missing_members {
+            bail!(
+                "users {} of expense {} are not members of group {}",
+                missing
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                id,
+                target_group_id
+            )
+        }
+        for user_id in missing {
+            let user = GroupUser {
+                user_id: Some(user_id),
+                ..GroupUser::default()
+            };
+            let response = self
+                .client
+                .groups()
+                .add_user_to_group(target_group_id, user)
+                .await?;
+            if !response.success {
+                match response.errors {
+                    Some(e) if !e.is_empty() => bail!(join_errors(&e)),
+                    _ => bail!(
+                        "unknown error adding user {} to group {}",
+                        user_id,
+                        target_group_id
+                    ),
+                }
+            }
+        }
+
+        // Shares are resent, as Splitwise would otherwise split the expense
+        // equally in the target group
+        let shares = expense
+            .users
+            .iter()
+            .flatten()
+            .map(|share| UserShare {
+                user_id: share.user_id(),
+                paid_share: share.paid_share.clone(),
+                owed_share: share.owed_share.clone(),
+                ..UserShare::default()
+            })
+            .collect::<Vec<_>>();
+        let request = UpdateExpenseRequest {
+            group_id: Some(target_group_id),
+            users: if shares.is_empty() {
+                None
+            } else {
+                Some(shares)
+            },
+            ..UpdateExpenseRequest::default()
+        };
+        self.update_expense(id, request).await
+    }
+}
+
+/// IDs of the users involved in an expense who are not members of the group.
+fn missing_members(expense: &Expense, group: &Group) -> Vec<i64> {
+    let members: Vec<i64> = group
+        .members
+        .iter()
+        .flatten()
+        .filter_map(|m| m.id)
+        .collect();
+    let mut missing = Vec::new();
+    for user_id in expense
+        .users
+        .iter()
+        .flatten()
+        .filter_map(UserShare::user_id)
+    {
+        if !members.contains(&user_id) && !missing.contains(&user_id) {
+            missing.push(user_id);
+        }
+    }
+    missing
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn missing_members_lists_users_outside_the_group() {
+        let expense: Expense = serde_json::from_value(json!({
+            "id": 1,
+            "users": [
+                {"user": {"id": 1}, "paid_share": "10.00", "owed_share": "5.00"},
+                {"user_id": 2, "paid_share": "0.00", "owed_share": "5.00"},
+                {"user_id": 3, "paid_share": "0.00", "owed_share": "0.00"}
+            ]
+        }))
+        .unwrap();
+        let group: Group = serde_json::from_value(json!({
+            "id": 7,
+            "members": [{"id": 1}, {"id": 4}]
+        }))
+        .unwrap();
+
+        assert_eq!(missing_members(&expense, &group), vec![2, 3]);
+    }
 }
 
 #[cfg(test)]
@@ -169,7 +428,7 @@ mod integration_tests {
     use test_log::test;
 
     use super::*;
-    use crate::model::expenses::UserShare;
+    use crate::client::rate_limit::RateLimit;
 
     #[test(tokio::test)]
     async fn create_update_get_delete_undelete_list_expense_works() {
@@ -188,7 +447,6 @@ mod integration_tests {
 
         let request = UpdateExpenseRequest {
             description: Some("new fake description".to_string()),
-            group_id: 0,
             ..UpdateExpenseRequest::default()
         };
         client.expenses().update_expense(id, request).await.unwrap();
@@ -215,7 +473,32 @@ mod integration_tests {
         let client = Client::default();
         let response = client.expenses().create_expense(request).await.unwrap();
         let expense_id = response.index(0).id.unwrap();
-        let _ = client.expenses().delete_expense(expense_id).await.unwrap();
+        client.expenses().delete_expense(expense_id).await.unwrap();
+    }
+
+    #[test(tokio::test)]
+    async fn create_delete_expenses_bulk_works() {
+        let client = Client::default().with_rate_limit(RateLimit::default());
+
+        let requests = (0..3)
+            .map(|i| CreateExpenseRequest {
+                cost: "1.00".to_string(),
+                description: format!("Fake bulk expense {}", i),
+                group_id: 0,
+                ..CreateExpenseRequest::default()
+            })
+            .collect();
+        let ids = client
+            .expenses()
+            .create_expenses(requests)
+            .await
+            .into_iter()
+            .map(|created| created.unwrap().index(0).id.unwrap())
+            .collect();
+
+        for deleted in client.expenses().delete_expenses(ids).await {
+            deleted.unwrap();
+        }
     }
 
     #[test(tokio::test)]
@@ -235,6 +518,6 @@ mod integration_tests {
         let client = Client::default();
         let response = client.expenses().create_expense(request).await.unwrap();
         let expense_id = response.index(0).id.unwrap();
-        let _ = client.expenses().delete_expense(expense_id).await.unwrap();
+        client.expenses().delete_expense(expense_id).await.unwrap();
     }
 }
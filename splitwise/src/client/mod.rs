@@ -1,21 +1,29 @@
 #![allow(clippy::module_inception)]
 
 pub(crate) mod authentication;
+pub(crate) mod cache;
 pub(crate) mod client;
 pub(crate) mod comments;
+pub(crate) mod dry_run;
 pub(crate) mod expenses;
 pub(crate) mod friends;
 pub(crate) mod groups;
 pub(crate) mod notifications;
 pub(crate) mod other;
+pub(crate) mod rate_limit;
+pub(crate) mod redact;
 pub(crate) mod users;
 
+#[allow(unused_imports)]
 pub use authentication::*;
+pub use cache::*;
 pub use client::*;
 pub use comments::*;
+pub use dry_run::*;
 pub use expenses::*;
 pub use friends::*;
 pub use groups::*;
 pub use notifications::*;
 pub use other::*;
+pub use rate_limit::*;
 pub use users::*;
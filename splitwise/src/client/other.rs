@@ -1,10 +1,16 @@
+use anyhow::anyhow;
+use anyhow::bail;
+
 use crate::client::client::Client;
+use crate::model::expenses::CreateExpenseRequest;
+use crate::model::expenses::Expense;
 use crate::model::other::CategoriesWrapper;
 use crate::model::other::Category;
 use crate::model::other::CurrenciesWrapper;
 use crate::model::other::Currency;
 use crate::model::other::ParseSentenceRequest;
 use crate::model::other::ParseSentenceResponse;
+use crate::model::other::SentencePreview;
 
 /// Other.
 ///
@@ -59,9 +65,65 @@ impl<'c> OtherSvc<'c> {
         request: ParseSentenceRequest,
     ) -> Result<ParseSentenceResponse, anyhow::Error> {
         let url = self.client.base_url.join("parse_sentence")?;
-        let response: ParseSentenceResponse = self.client.post(url, &request).await?;
+        // Only saving the parsed expense modifies data
+        let response: ParseSentenceResponse = if request.autosave {
+            self.client.post(url, &request).await?
+        } else {
+            self.client.post_read_only(url, &request).await?
+        };
         Ok(response)
     }
+
+    /// Parses the input like `parse_sentence`, but never saves the result.
+    /// Instead, returns a preview containing the request that would create the
+    /// parsed expense, which may be inspected and later passed to
+    /// `commit_sentence`.
+    ///
+    /// Fails if Splitwise reports an error, if the parsed expense is not
+    /// valid, or if its confidence is below `min_confidence`.
+    pub async fn preview_sentence(
+        &self,
+        request: ParseSentenceRequest,
+        min_confidence: f64,
+    ) -> Result<SentencePreview, anyhow::Error> {
+        let request = ParseSentenceRequest {
+            autosave: false,
+            ..request
+        };
+        let response = self.parse_sentence(request).await?;
+
+        if let Some(e) = response.error {
+            bail!(e)
+        }
+        if !response.valid.unwrap_or(false) {
+            bail!("sentence did not parse into a valid expense")
+        }
+        let confidence = response.confidence.unwrap_or(0.0);
+        if confidence < min_confidence {
+            bail!(
+                "parsed expense confidence {} is below the minimum of {}",
+                confidence,
+                min_confidence
+            )
+        }
+        let expense = response
+            .expense
+            .ok_or_else(|| anyhow!("sentence parsed as valid but no expense was returned"))?;
+
+        Ok(SentencePreview {
+            request: CreateExpenseRequest::from(&expense),
+            expense,
+            confidence,
+        })
+    }
+
+    /// Creates the expense described by a preview from `preview_sentence`.
+    pub async fn commit_sentence(
+        &self,
+        preview: SentencePreview,
+    ) -> Result<Vec<Expense>, anyhow::Error> {
+        self.client.expenses().create_expense(preview.request).await
+    }
 }
 
 #[cfg(test)]
@@ -93,4 +155,19 @@ mod integration_tests {
             .unwrap();
         assert!(response.valid.unwrap());
     }
+
+    #[test(tokio::test)]
+    async fn preview_commit_sentence_works() {
+        let client = Client::default();
+
+        let request = ParseSentenceRequest {
+            input: "paid $12 for fake sentence tacos split evenly".to_string(),
+            ..ParseSentenceRequest::default()
+        };
+        let preview = client.other().preview_sentence(request, 0.0).await.unwrap();
+
+        let created = client.other().commit_sentence(preview).await.unwrap();
+        let id = created.first().unwrap().id.unwrap();
+        client.expenses().delete_expense(id).await.unwrap();
+    }
 }
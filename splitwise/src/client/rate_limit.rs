@@ -0,0 +1,149 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::sync::Semaphore;
+use tokio::sync::SemaphorePermit;
+
+/// Limits on how quickly a `Client` sends requests to Splitwise.
+///
+/// Requests are throttled with a token bucket that refills at
+/// `requests_per_second` and holds at most `burst` tokens. Independently, no
+/// more than `max_in_flight` requests may await a response at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained number of requests sent per second. Must be finite and
+    /// positive.
+    pub requests_per_second: f64,
+
+    /// Number of requests that may be sent back-to-back before throttling
+    /// kicks in.
+    pub burst: u32,
+
+    /// Maximum number of requests awaiting a response at any time.
+    pub max_in_flight: usize,
+
+    /// Number of times a request rejected with `429 Too Many Requests` is
+    /// retried before giving up.
+    pub max_retries: u32,
+}
+
+impl Default for RateLimit {
+    /// Conservative limits suitable for bulk operations against the official
+    /// Splitwise API, which does not publish its limits.
+    fn default() -> Self {
+        Self {
+            requests_per_second: 2.0,
+            burst: 5,
+            max_in_flight: 4,
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Shared state enforcing a `RateLimit` across clones of a `Client`.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    pub(crate) limit: RateLimit,
+    bucket: Mutex<TokenBucket>,
+    in_flight: Semaphore,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        assert!(
+            limit.requests_per_second.is_finite() && limit.requests_per_second > 0.0,
+            "requests_per_second must be finite and positive, got {}",
+            limit.requests_per_second
+        );
+        Self {
+            limit,
+            bucket: Mutex::new(TokenBucket {
+                tokens: f64::from(limit.burst.max(1)),
+                last_refill: Instant::now(),
+            }),
+            in_flight: Semaphore::new(limit.max_in_flight.max(1)),
+        }
+    }
+
+    /// Waits until a request may be sent. The request counts as in flight
+    /// until the returned permit is dropped.
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        // The semaphore is never closed, so acquiring cannot fail
+        let permit = self.in_flight.acquire().await.unwrap();
+        while let Some(wait) = self.take_token() {
+            tokio::time::sleep(wait).await;
+        }
+        permit
+    }
+
+    /// Takes a token from the bucket if one is available, otherwise returns
+    /// how long to wait until one will be.
+    fn take_token(&self) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let capacity = f64::from(self.limit.burst.max(1));
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.limit.requests_per_second).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        let missing = 1.0 - bucket.tokens;
+        Some(Duration::from_secs_f64(
+            missing / self.limit.requests_per_second,
+        ))
+    }
+
+    /// Delay before retrying a request that was rejected for the given number
+    /// of consecutive times.
+    pub(crate) fn backoff(&self, retries: u32) -> Duration {
+        Duration::from_millis(500 * 2u64.pow(retries.min(6)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn acquire_throttles_after_burst() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_second: 20.0,
+            burst: 2,
+            max_in_flight: 1,
+            max_retries: 0,
+        });
+
+        let start = Instant::now();
+        for _ in 0..6 {
+            drop(limiter.acquire().await);
+        }
+
+        // Two requests use up the burst, the remaining four wait 50ms each
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+
+    #[tokio::test]
+    async fn acquire_limits_in_flight() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_second: 1000.0,
+            burst: 10,
+            max_in_flight: 2,
+            max_retries: 0,
+        });
+
+        let _first = limiter.acquire().await;
+        let _second = limiter.acquire().await;
+        let third = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(third.is_err());
+    }
+}
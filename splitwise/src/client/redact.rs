@@ -0,0 +1,109 @@
+use reqwest::header::HeaderMap;
+use reqwest::header::AUTHORIZATION;
+use serde_json::Value;
+
+/// Placeholder for values that must never be logged.
+const REDACTED: &str = "[REDACTED]";
+
+/// Field names, or parts of field names, whose values are redacted. Matching
+/// is by substring so flattened keys like `users__0__email` are covered.
+const SENSITIVE_KEYS: &[&str] = &["email", "password", "token", "secret"];
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SENSITIVE_KEYS.iter().any(|k| key.contains(k))
+}
+
+/// Formats headers for logging, with the `Authorization` header redacted.
+pub(crate) fn redact_headers(headers: &HeaderMap) -> String {
+    let mut text = Vec::new();
+    for (name, value) in headers {
+        let value = if name == AUTHORIZATION {
+            REDACTED
+        } else {
+            value.to_str().unwrap_or("<non-ascii>")
+        };
+        text.push(format!("{}: {}", name, value));
+    }
+    text.join(", ")
+}
+
+/// Formats a JSON or form-encoded body for logging, with sensitive values
+/// redacted.
+pub(crate) fn redact_body(body: &[u8]) -> String {
+    if let Ok(mut json) = serde_json::from_slice::<Value>(body) {
+        redact_json(&mut json);
+        return json.to_string();
+    }
+
+    let text = match std::str::from_utf8(body) {
+        Ok(text) => text,
+        Err(_) => return format!("<{} bytes>", body.len()),
+    };
+    if !text.contains('=') {
+        return text.to_string();
+    }
+    let mut form = url::form_urlencoded::Serializer::new(String::new());
+    for (k, v) in url::form_urlencoded::parse(text.as_bytes()) {
+        if is_sensitive(&k) {
+            form.append_pair(&k, REDACTED);
+        } else {
+            form.append_pair(&k, &v);
+        }
+    }
+    form.finish()
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if is_sensitive(k) && !v.is_null() {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+    use reqwest::header::CONTENT_TYPE;
+
+    use super::*;
+
+    #[test]
+    fn redact_headers_hides_authorization() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer hunter2"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let text = redact_headers(&headers);
+        assert!(!text.contains("hunter2"));
+        assert!(text.contains("application/json"));
+    }
+
+    #[test]
+    fn redact_body_hides_nested_json_secrets() {
+        let body = br#"{"password":"hunter2","first_name":"Ada","user":{"email":"ada@example.com"},"users__0__email":"b@example.com","users":[{"email":"c@example.com"}]}"#;
+
+        let text = redact_body(body);
+        assert!(!text.contains("hunter2"));
+        assert!(!text.contains("example.com"));
+        assert!(text.contains("Ada"));
+    }
+
+    #[test]
+    fn redact_body_hides_form_secrets() {
+        let body = b"users%5B0%5D%5Bemail%5D=ada%40example.com&message=hi";
+
+        let text = redact_body(body);
+        assert!(!text.contains("example.com"));
+        assert!(text.contains("message=hi"));
+    }
+}
@@ -0,0 +1,273 @@
+//! Passphrase-encrypted file holding a Splitwise API key or OAuth token.
+//!
+//! The encryption key is derived from the passphrase with Argon2id and a
+//! random salt, and the credential is sealed with ChaCha20-Poly1305 under a
+//! random nonce. The file is JSON holding the KDF parameters, salt, nonce and
+//! ciphertext, so a wrong passphrase or a tampered file fails to decrypt
+//! rather than yielding a wrong credential. Plaintext credentials only live
+//! in memory, in zeroized buffers and `Secret`s.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use argon2::Algorithm;
+use argon2::Argon2;
+use argon2::Params;
+use argon2::Version;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::AeadCore;
+use chacha20poly1305::aead::KeyInit;
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::ChaCha20Poly1305;
+use chacha20poly1305::Key;
+use chacha20poly1305::Nonce;
+use secrecy::zeroize::Zeroizing;
+use secrecy::ExposeSecret;
+use secrecy::Secret;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::client::Client;
+
+/// Version of the file format written by `CredentialStore::save`.
+const STORE_VERSION: u32 = 1;
+
+/// Length in bytes of the random KDF salt.
+const SALT_LEN: usize = 16;
+
+/// Splitwise credential, sent as a bearer token.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    /// API key from the Splitwise developer portal.
+    ApiKey(Secret<String>),
+    /// OAuth 2.0 access token.
+    OAuthToken(Secret<String>),
+}
+
+/// Encrypted credential file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialStore {
+    path: PathBuf,
+}
+
+/// On-disk format of the store.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Argon2id cost parameters, kept in the file so they can be raised later
+/// without breaking existing files.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Plaintext sealed in the store, borrowed when sealing so that the secret
+/// is not copied.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "secret", rename_all = "snake_case")]
+enum Sealed<S> {
+    ApiKey(S),
+    OauthToken(S),
+}
+
+impl CredentialStore {
+    /// Creates a store backed by the file at the given path, which does not
+    /// need to exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Path of the file backing the store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a credential was saved.
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Encrypts the credential with the passphrase and writes it to the
+    /// store, replacing any previous credential. The file is only readable by
+    /// its owner on Unix.
+    pub fn save(
+        &self,
+        credential: &Credential,
+        passphrase: &Secret<String>,
+    ) -> Result<(), anyhow::Error> {
+        let kdf = KdfParams::default();
+        let mut salt = [0u8; SALT_LEN];
+        chacha20poly1305::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+        let key = derive_key(passphrase, &salt, kdf)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let sealed = match credential {
+            Credential::ApiKey(key) => Sealed::ApiKey(key.expose_secret().as_str()),
+            Credential::OAuthToken(token) => Sealed::OauthToken(token.expose_secret().as_str()),
+        };
+        let plaintext = Zeroizing::new(serde_json::to_vec(&sealed)?);
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&*key))
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow!("encrypting credential failed"))?;
+
+        let file = StoreFile {
+            version: STORE_VERSION,
+            kdf,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut writer = options
+            .open(&tmp)
+            .with_context(|| format!("writing credentials {}", tmp.display()))?;
+        writer.write_all(&serde_json::to_vec_pretty(&file)?)?;
+        writer.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Reads and decrypts the credential with the passphrase.
+    pub fn load(&self, passphrase: &Secret<String>) -> Result<Credential, anyhow::Error> {
+        let contents = std::fs::read(&self.path)
+            .with_context(|| format!("reading credentials {}", self.path.display()))?;
+        let file: StoreFile = serde_json::from_slice(&contents)
+            .with_context(|| format!("parsing credentials {}", self.path.display()))?;
+        if file.version > STORE_VERSION {
+            bail!(
+                "credentials version {} is newer than the supported version {}",
+                file.version,
+                STORE_VERSION
+            )
+        }
+
+        let salt = BASE64.decode(&file.salt)?;
+        let nonce = BASE64.decode(&file.nonce)?;
+        if nonce.len() != 12 {
+            bail!("invalid nonce in credentials {}", self.path.display())
+        }
+        let ciphertext = BASE64.decode(&file.ciphertext)?;
+        let key = derive_key(passphrase, &salt, file.kdf)?;
+        let plaintext = Zeroizing::new(
+            ChaCha20Poly1305::new(Key::from_slice(&*key))
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| anyhow!("wrong passphrase, or corrupted credentials"))?,
+        );
+
+        let sealed: Sealed<String> = serde_json::from_slice(&plaintext)?;
+        Ok(match sealed {
+            Sealed::ApiKey(key) => Credential::ApiKey(key.into()),
+            Sealed::OauthToken(token) => Credential::OAuthToken(token.into()),
+        })
+    }
+
+    /// Overwrites the file with zeros and removes it. Does nothing if no
+    /// credential was saved.
+    pub fn wipe(&self) -> Result<(), anyhow::Error> {
+        let len = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut file = OpenOptions::new().write(true).open(&self.path)?;
+        file.write_all(&vec![0u8; len as usize])?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+fn derive_key(
+    passphrase: &Secret<String>,
+    salt: &[u8],
+    kdf: KdfParams,
+) -> Result<Zeroizing<[u8; 32]>, anyhow::Error> {
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| anyhow!("invalid KDF parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.expose_secret().as_bytes(), salt, &mut *key)
+        .map_err(|e| anyhow!("deriving key failed: {}", e))?;
+    Ok(key)
+}
+
+impl Client {
+    /// Builds a new Splitwise client from the current one, with the
+    /// credential decrypted from the store as an override.
+    pub fn with_credential_store(
+        self,
+        store: &CredentialStore,
+        passphrase: &Secret<String>,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(match store.load(passphrase)? {
+            Credential::ApiKey(key) => self.with_api_key(key),
+            Credential::OAuthToken(token) => self.with_oauth_token(token),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_round_trips_and_rejects_wrong_passphrase() {
+        let dir =
+            std::env::temp_dir().join(format!("splitwise-credentials-{}", std::process::id()));
+        let store = CredentialStore::new(dir.join("credentials.json"));
+        let passphrase: Secret<String> = "correct horse".to_string().into();
+
+        store
+            .save(
+                &Credential::ApiKey("api-key-123".to_string().into()),
+                &passphrase,
+            )
+            .unwrap();
+        let contents = std::fs::read_to_string(store.path()).unwrap();
+        assert!(!contents.contains("api-key-123"));
+
+        match store.load(&passphrase).unwrap() {
+            Credential::ApiKey(key) => assert_eq!(key.expose_secret(), "api-key-123"),
+            Credential::OAuthToken(_) => panic!("expected an API key"),
+        }
+        let wrong: Secret<String> = "battery staple".to_string().into();
+        assert!(store.load(&wrong).is_err());
+
+        store.wipe().unwrap();
+        assert!(!store.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
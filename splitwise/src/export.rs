@@ -0,0 +1,257 @@
+//! Flat exports of expenses for spreadsheets.
+//!
+//! Each expense becomes one CSV row with its date, description, category,
+//! cost, currency and payers, followed by a paid and an owed column for every
+//! user involved in any of the exported expenses.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::Utc;
+use rust_decimal::Decimal;
+
+use crate::client::Client;
+use crate::model::expenses::Expense;
+use crate::model::expenses::ListExpensesRequest;
+use crate::model::expenses::UserShare;
+use crate::model::other::Category;
+
+/// Selects the expenses written by `export_expenses_csv`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ExpenseExportOptions {
+    /// Only export expenses in this group.
+    pub group_id: Option<i64>,
+
+    /// Only export expenses dated after this date.
+    pub dated_after: Option<DateTime<Utc>>,
+
+    /// Only export expenses dated before this date.
+    pub dated_before: Option<DateTime<Utc>>,
+
+    /// Also export deleted expenses, marking them in a `Deleted` column.
+    pub include_deleted: bool,
+}
+
+/// Fetches the expenses selected by the options and writes them as CSV.
+/// Returns the number of expenses written.
+pub async fn export_expenses_csv<W: Write>(
+    client: &Client,
+    options: &ExpenseExportOptions,
+    writer: W,
+) -> Result<usize, anyhow::Error> {
+    let expenses = client
+        .expenses()
+        .list_all_expenses(ListExpensesRequest {
+            group_id: options.group_id,
+            dated_after: options.dated_after,
+            dated_before: options.dated_before,
+            ..ListExpensesRequest::default()
+        })
+        .await?;
+    let categories = client.other().get_categories().await?;
+    write_expenses_csv(&expenses, &categories, options.include_deleted, writer)
+}
+
+/// Writes expenses as CSV, looking up category names in `categories`.
+/// Deleted expenses are skipped unless `include_deleted` is set. Returns the
+/// number of expenses written.
+pub fn write_expenses_csv<W: Write>(
+    expenses: &[Expense],
+    categories: &[Category],
+    include_deleted: bool,
+    writer: W,
+) -> Result<usize, anyhow::Error> {
+    let expenses: Vec<&Expense> = expenses
+        .iter()
+        .filter(|e| include_deleted || e.deleted_at.is_none())
+        .collect();
+
+    let category_names: HashMap<i64, String> = categories
+        .iter()
+        .flat_map(|c| std::iter::once(c).chain(c.subcategories.iter().flatten()))
+        .filter_map(|c| Some((c.id?, c.name.clone()?)))
+        .collect();
+
+    // One pair of columns per user, in order of first appearance
+    let mut members: Vec<(i64, String)> = Vec::new();
+    for share in expenses.iter().flat_map(|e| e.users.iter().flatten()) {
+        if let Some(id) = share.user_id() {
+            if !members.iter().any(|(m, _)| *m == id) {
+                members.push((id, share_user_name(share, id)));
+            }
+        }
+    }
+    // Tell apart users with the same name by their ID
+    let names: Vec<String> = members.iter().map(|(_, name)| name.clone()).collect();
+    for (id, name) in members.iter_mut() {
+        if names.iter().filter(|n| *n == name).count() > 1 {
+            *name = format!("{} ({})", name, id);
+        }
+    }
+
+    let mut csv = csv::Writer::from_writer(writer);
+    let mut header: Vec<String> = [
+        "Date",
+        "Description",
+        "Category",
+        "Cost",
+        "Currency",
+        "Paid by",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect();
+    for (_, name) in members.iter() {
+        header.push(format!("{} paid", name));
+        header.push(format!("{} owed", name));
+    }
+    if include_deleted {
+        header.push("Deleted".to_string());
+    }
+    csv.write_record(&header)?;
+
+    for expense in expenses.iter() {
+        let shares: HashMap<i64, &UserShare> = expense
+            .users
+            .iter()
+            .flatten()
+            .filter_map(|s| s.user_id().map(|id| (id, s)))
+            .collect();
+        let payers: Vec<String> = members
+            .iter()
+            .filter(|(id, _)| {
+                shares
+                    .get(id)
+                    .and_then(|s| s.paid_share.as_ref())
+                    .and_then(|p| Decimal::from_str(p.trim()).ok())
+                    .map_or(false, |p| p > Decimal::ZERO)
+            })
+            .map(|(_, name)| name.clone())
+            .collect();
+        let category = expense
+            .category
+            .as_ref()
+            .and_then(|c| c.name.clone())
+            .or_else(|| {
+                expense
+                    .category_id
+                    .and_then(|id| category_names.get(&id).cloned())
+            })
+            .unwrap_or_default();
+
+        let mut record = vec![
+            expense
+                .date
+                .map(|d| d.date_naive().to_string())
+                .unwrap_or_default(),
+            expense.description.clone().unwrap_or_default(),
+            category,
+            expense.cost.clone().unwrap_or_default(),
+            expense.currency_code.clone().unwrap_or_default(),
+            payers.join("; "),
+        ];
+        for (id, _) in members.iter() {
+            let share = shares.get(id);
+            record.push(share.and_then(|s| s.paid_share.clone()).unwrap_or_default());
+            record.push(share.and_then(|s| s.owed_share.clone()).unwrap_or_default());
+        }
+        if include_deleted {
+            record.push(expense.deleted_at.is_some().to_string());
+        }
+        csv.write_record(&record)?;
+    }
+
+    csv.flush()?;
+    Ok(expenses.len())
+}
+
+fn share_user_name(share: &UserShare, id: i64) -> String {
+    let user = share.user.as_ref();
+    let first = user
+        .and_then(|u| u.first_name.clone())
+        .or_else(|| share.first_name.clone());
+    let last = user
+        .and_then(|u| u.last_name.clone())
+        .or_else(|| share.last_name.clone());
+    match (first, last) {
+        (Some(first), Some(last)) => format!("{} {}", first, last),
+        (Some(first), None) => first,
+        _ => format!("User {}", id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn write_expenses_csv_flattens_shares() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([
+            {
+                "id": 1,
+                "date": "2023-03-01T12:00:00Z",
+                "description": "Groceries",
+                "cost": "30.00",
+                "currency_code": "USD",
+                "category_id": 12,
+                "users": [
+                    {"user": {"id": 1, "first_name": "Ada", "last_name": "Lovelace"}, "paid_share": "30.00", "owed_share": "15.00"},
+                    {"user": {"id": 2, "first_name": "Alan"}, "paid_share": "0.00", "owed_share": "15.00"}
+                ]
+            },
+            {
+                "id": 2,
+                "date": "2023-03-02T12:00:00Z",
+                "description": "Deleted",
+                "cost": "5.00",
+                "deleted_at": "2023-03-03T12:00:00Z"
+            }
+        ]))
+        .unwrap();
+        let categories: Vec<Category> = serde_json::from_value(json!([
+            {"id": 1, "name": "Food", "subcategories": [{"id": 12, "name": "Groceries"}]}
+        ]))
+        .unwrap();
+
+        let mut out = Vec::new();
+        let written = write_expenses_csv(&expenses, &categories, false, &mut out).unwrap();
+        assert_eq!(written, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Date,Description,Category,Cost,Currency,Paid by,Ada Lovelace paid,Ada Lovelace owed,Alan paid,Alan owed\n\
+             2023-03-01,Groceries,Groceries,30.00,USD,Ada Lovelace,30.00,15.00,0.00,15.00\n"
+        );
+
+        let mut out = Vec::new();
+        let written = write_expenses_csv(&expenses, &categories, true, &mut out).unwrap();
+        assert_eq!(written, 2);
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("5.00,,,,,,,true\n"));
+    }
+
+    #[test]
+    fn write_expenses_csv_tells_apart_same_names() {
+        let expenses: Vec<Expense> = serde_json::from_value(json!([{
+            "id": 1,
+            "cost": "10.00",
+            "users": [
+                {"user": {"id": 1, "first_name": "Sam"}, "paid_share": "10.00", "owed_share": "5.00"},
+                {"user": {"id": 2, "first_name": "Sam"}, "paid_share": "0.00", "owed_share": "5.00"}
+            ]
+        }]))
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_expenses_csv(&expenses, &[], false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Date,Description,Category,Cost,Currency,Paid by,Sam (1) paid,Sam (1) owed,Sam (2) paid,Sam (2) owed\n\
+             ,,,10.00,,Sam (1),10.00,5.00,0.00,5.00\n"
+        );
+    }
+}
@@ -1,4 +1,12 @@
 #![doc = include_str!("../../README.md")]
 
+pub mod archive;
+pub mod bulk;
 pub mod client;
+#[cfg(feature = "credential-store")]
+pub mod credentials;
+pub mod export;
+#[cfg(feature = "mirror")]
+pub mod mirror;
 pub mod model;
+pub mod rollover;
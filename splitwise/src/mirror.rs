@@ -0,0 +1,610 @@
+//! Local SQLite copy of a Splitwise account, for offline queries and reports.
+//!
+//! The first sync downloads everything. Later syncs only download expenses
+//! updated since the previous one, using `ListExpensesRequest.updated_after`,
+//! and mark expenses with a `deleted_at` as deleted rather than removing them.
+//! Groups, friends and categories are small and are always fully refreshed.
+//!
+//! Dates are stored as RFC 3339 text and decimal amounts as the strings
+//! returned by Splitwise. Deleted expenses are kept, so queries should usually
+//! filter on `expenses.deleted_at IS NULL`.
+
+use std::path::Path;
+
+use chrono::DateTime;
+use chrono::Utc;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::Transaction;
+
+use crate::client::Client;
+use crate::model::comments::Comment;
+use crate::model::expenses::Expense;
+use crate::model::expenses::ListExpensesRequest;
+use crate::model::groups::Group;
+use crate::model::other::Category;
+use crate::model::users::User;
+
+/// Key in `sync_state` holding the latest `updated_at` of a synced expense.
+const EXPENSES_UPDATED_AT: &str = "expenses_updated_at";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY,
+    first_name TEXT,
+    last_name TEXT,
+    email TEXT,
+    registration_status TEXT,
+    default_currency TEXT,
+    locale TEXT,
+    is_current_user INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS friends (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id),
+    updated_at TEXT
+);
+CREATE TABLE IF NOT EXISTS friend_balances (
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    currency_code TEXT NOT NULL,
+    amount TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS groups (
+    id INTEGER PRIMARY KEY,
+    name TEXT,
+    group_type TEXT,
+    simplify_by_default INTEGER,
+    updated_at TEXT
+);
+CREATE TABLE IF NOT EXISTS group_members (
+    group_id INTEGER NOT NULL REFERENCES groups(id),
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    PRIMARY KEY (group_id, user_id)
+);
+CREATE TABLE IF NOT EXISTS group_debts (
+    group_id INTEGER NOT NULL REFERENCES groups(id),
+    from_user_id INTEGER NOT NULL,
+    to_user_id INTEGER NOT NULL,
+    amount TEXT NOT NULL,
+    currency_code TEXT
+);
+CREATE TABLE IF NOT EXISTS categories (
+    id INTEGER PRIMARY KEY,
+    name TEXT,
+    parent_id INTEGER REFERENCES categories(id)
+);
+CREATE TABLE IF NOT EXISTS expenses (
+    id INTEGER PRIMARY KEY,
+    group_id INTEGER,
+    friendship_id INTEGER,
+    description TEXT,
+    details TEXT,
+    cost TEXT,
+    currency_code TEXT,
+    category_id INTEGER,
+    date TEXT,
+    payment INTEGER,
+    repeats INTEGER,
+    repeat_interval TEXT,
+    comments_count INTEGER,
+    created_at TEXT,
+    created_by INTEGER,
+    updated_at TEXT,
+    deleted_at TEXT
+);
+CREATE INDEX IF NOT EXISTS expenses_group_date ON expenses (group_id, date);
+CREATE TABLE IF NOT EXISTS expense_shares (
+    expense_id INTEGER NOT NULL REFERENCES expenses(id),
+    user_id INTEGER NOT NULL,
+    paid_share TEXT,
+    owed_share TEXT,
+    net_balance TEXT,
+    PRIMARY KEY (expense_id, user_id)
+);
+CREATE TABLE IF NOT EXISTS comments (
+    id INTEGER PRIMARY KEY,
+    expense_id INTEGER NOT NULL REFERENCES expenses(id),
+    user_id INTEGER,
+    content TEXT NOT NULL,
+    comment_type TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE TABLE IF NOT EXISTS sync_state (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+";
+
+/// Counts of records written by `Mirror::sync`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Number of groups refreshed.
+    pub groups: usize,
+
+    /// Number of friends refreshed.
+    pub friends: usize,
+
+    /// Number of categories and subcategories refreshed.
+    pub categories: usize,
+
+    /// Number of expenses created or updated since the previous sync.
+    pub expenses_updated: usize,
+
+    /// Number of expenses deleted since the previous sync.
+    pub expenses_deleted: usize,
+
+    /// Number of comments refreshed.
+    pub comments: usize,
+}
+
+/// Local SQLite copy of a Splitwise account.
+#[derive(Debug)]
+pub struct Mirror {
+    conn: Connection,
+}
+
+impl Mirror {
+    /// Opens the mirror database at the given path, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens a mirror database that only lives in memory.
+    pub fn open_in_memory() -> Result<Self, anyhow::Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, anyhow::Error> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Connection to the mirror database, for running offline queries.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Latest `updated_at` of the expenses synced so far, or `None` if no
+    /// expenses have been synced.
+    pub fn last_synced_at(&self) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+        let value: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?1",
+                params![EXPENSES_UPDATED_AT],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match value {
+            Some(v) => Ok(Some(DateTime::parse_from_rfc3339(&v)?.with_timezone(&Utc))),
+            None => Ok(None),
+        }
+    }
+
+    /// Refreshes the mirror from Splitwise. Only expenses updated since the
+    /// previous sync are downloaded, and the stored comments of each are
+    /// replaced with its current ones, so that removed comments go too.
+    pub async fn sync(&mut self, client: &Client) -> Result<SyncReport, anyhow::Error> {
+        let current_user = client.users().get_current_user().await?;
+        let friends = client.friends().list_friends().await?;
+        let groups = client.groups().list_groups().await?;
+        let categories = client.other().get_categories().await?;
+
+        let expenses = client
+            .expenses()
+            .list_all_expenses(ListExpensesRequest {
+                updated_after: self.last_synced_at()?,
+                ..ListExpensesRequest::default()
+            })
+            .await?;
+
+        let mut comments = Vec::new();
+        for expense in expenses.iter() {
+            let id = match expense.id {
+                Some(id) if expense.deleted_at.is_none() => id,
+                _ => continue,
+            };
+            // Expenses without comments may have had some removed
+            let expense_comments = if expense.comments_count.unwrap_or(0) > 0 {
+                client.comments().get_comments(id).await?
+            } else {
+                Vec::new()
+            };
+            comments.push((id, expense_comments));
+        }
+
+        let tx = self.conn.transaction()?;
+        store_user(&tx, &current_user, true)?;
+        let mut report = SyncReport {
+            friends: store_friends(&tx, &friends)?,
+            groups: store_groups(&tx, &groups)?,
+            categories: store_categories(&tx, &categories)?,
+            ..SyncReport::default()
+        };
+        let (updated, deleted) = store_expenses(&tx, &expenses)?;
+        report.expenses_updated = updated;
+        report.expenses_deleted = deleted;
+        for (expense_id, expense_comments) in comments.iter() {
+            report.comments += store_comments(&tx, *expense_id, expense_comments)?;
+        }
+        tx.commit()?;
+
+        Ok(report)
+    }
+}
+
+fn to_text(datetime: Option<DateTime<Utc>>) -> Option<String> {
+    datetime.map(|d| d.to_rfc3339())
+}
+
+fn store_user(tx: &Transaction, user: &User, is_current_user: bool) -> Result<(), anyhow::Error> {
+    let id = match user.id {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    tx.execute(
+        "INSERT INTO users (id, first_name, last_name, email, registration_status,
+                            default_currency, locale, is_current_user)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT (id) DO UPDATE SET
+            first_name = excluded.first_name,
+            last_name = excluded.last_name,
+            email = COALESCE(excluded.email, users.email),
+            registration_status = excluded.registration_status,
+            default_currency = COALESCE(excluded.default_currency, users.default_currency),
+            locale = COALESCE(excluded.locale, users.locale),
+            is_current_user = MAX(users.is_current_user, excluded.is_current_user)",
+        params![
+            id,
+            user.first_name,
+            user.last_name,
+            user.email,
+            user.registration_status,
+            user.default_currency,
+            user.locale,
+            is_current_user,
+        ],
+    )?;
+    Ok(())
+}
+
+fn store_friends(tx: &Transaction, friends: &[User]) -> Result<usize, anyhow::Error> {
+    tx.execute("DELETE FROM friend_balances", [])?;
+    tx.execute("DELETE FROM friends", [])?;
+    let mut count = 0;
+    for friend in friends {
+        let id = match friend.id {
+            Some(id) => id,
+            None => continue,
+        };
+        store_user(tx, friend, false)?;
+        tx.execute(
+            "INSERT INTO friends (user_id, updated_at) VALUES (?1, ?2)",
+            params![id, to_text(friend.updated_at)],
+        )?;
+        for balance in friend.balance.iter().flatten() {
+            tx.execute(
+                "INSERT INTO friend_balances (user_id, currency_code, amount)
+                 VALUES (?1, ?2, ?3)",
+                params![id, balance.currency_code, balance.amount],
+            )?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn store_groups(tx: &Transaction, groups: &[Group]) -> Result<usize, anyhow::Error> {
+    tx.execute("DELETE FROM group_debts", [])?;
+    tx.execute("DELETE FROM group_members", [])?;
+    tx.execute("DELETE FROM groups", [])?;
+    let mut count = 0;
+    for group in groups {
+        let id = match group.id {
+            Some(id) => id,
+            None => continue,
+        };
+        tx.execute(
+            "INSERT INTO groups (id, name, group_type, simplify_by_default, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                group.name,
+                group.group_type,
+                group.simplify_by_default,
+                to_text(group.updated_at),
+            ],
+        )?;
+        for member in group.members.iter().flatten() {
+            store_user(tx, member, false)?;
+            if let Some(user_id) = member.id {
+                tx.execute(
+                    "INSERT OR IGNORE INTO group_members (group_id, user_id) VALUES (?1, ?2)",
+                    params![id, user_id],
+                )?;
+            }
+        }
+        for debt in group.simplified_debts.iter().flatten() {
+            tx.execute(
+                "INSERT INTO group_debts (group_id, from_user_id, to_user_id, amount, currency_code)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id,
+                    debt.from,
+                    debt.to,
+                    debt.amount.as_deref().unwrap_or("0"),
+                    debt.currency_code,
+                ],
+            )?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn store_categories(tx: &Transaction, categories: &[Category]) -> Result<usize, anyhow::Error> {
+    tx.execute("DELETE FROM categories", [])?;
+    let mut count = 0;
+    for parent in categories {
+        tx.execute(
+            "INSERT OR REPLACE INTO categories (id, name, parent_id) VALUES (?1, ?2, NULL)",
+            params![parent.id, parent.name],
+        )?;
+        count += 1;
+        for child in parent.subcategories.iter().flatten() {
+            tx.execute(
+                "INSERT OR REPLACE INTO categories (id, name, parent_id) VALUES (?1, ?2, ?3)",
+                params![child.id, child.name, parent.id],
+            )?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Stores expenses and their shares, returning the number of updated and of
+/// deleted expenses.
+fn store_expenses(tx: &Transaction, expenses: &[Expense]) -> Result<(usize, usize), anyhow::Error> {
+    let (mut updated, mut deleted) = (0, 0);
+    let mut latest = None;
+    for expense in expenses {
+        let id = match expense.id {
+            Some(id) => id,
+            None => continue,
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO expenses (id, group_id, friendship_id, description,
+                details, cost, currency_code, category_id, date, payment, repeats,
+                repeat_interval, comments_count, created_at, created_by, updated_at,
+                deleted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                ?16, ?17)",
+            params![
+                id,
+                expense.group_id,
+                expense.friendship_id,
+                expense.description,
+                expense.details,
+                expense.cost,
+                expense.currency_code,
+                expense
+                    .category_id
+                    .or_else(|| expense.category.as_ref().and_then(|c| c.id)),
+                to_text(expense.date),
+                expense.payment,
+                expense.repeats,
+                expense.repeat_interval,
+                expense.comments_count,
+                to_text(expense.created_at),
+                expense.created_by.as_ref().and_then(|u| u.id),
+                to_text(expense.updated_at),
+                to_text(expense.deleted_at),
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM expense_shares WHERE expense_id = ?1",
+            params![id],
+        )?;
+        for share in expense.users.iter().flatten() {
+            if let Some(ref user) = share.user {
+                store_user(tx, user, false)?;
+            }
+            let user_id = match share.user_id() {
+                Some(user_id) => user_id,
+                None => continue,
+            };
+            tx.execute(
+                "INSERT OR REPLACE INTO expense_shares (expense_id, user_id, paid_share,
+                    owed_share, net_balance)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id,
+                    user_id,
+                    share.paid_share,
+                    share.owed_share,
+                    share.net_balance,
+                ],
+            )?;
+        }
+
+        if expense.deleted_at.is_some() {
+            deleted += 1;
+        } else {
+            updated += 1;
+        }
+        latest = latest.max(expense.updated_at);
+    }
+
+    if let Some(latest) = latest {
+        let previous: Option<String> = tx
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?1",
+                params![EXPENSES_UPDATED_AT],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let previous = previous
+            .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+            .map(|d| d.with_timezone(&Utc));
+        if previous.map_or(true, |p| latest > p) {
+            tx.execute(
+                "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?1, ?2)",
+                params![EXPENSES_UPDATED_AT, latest.to_rfc3339()],
+            )?;
+        }
+    }
+
+    Ok((updated, deleted))
+}
+
+/// Replaces the stored comments of an expense.
+fn store_comments(
+    tx: &Transaction,
+    expense_id: i64,
+    comments: &[Comment],
+) -> Result<usize, anyhow::Error> {
+    tx.execute(
+        "DELETE FROM comments WHERE expense_id = ?1",
+        params![expense_id],
+    )?;
+    for comment in comments {
+        if let Some(ref user) = comment.user {
+            store_user(tx, user, false)?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO comments (id, expense_id, user_id, content, comment_type,
+                created_at, deleted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                comment.id,
+                expense_id,
+                comment.user.as_ref().and_then(|u| u.id),
+                comment.content,
+                comment.comment_type,
+                comment.created_at.to_rfc3339(),
+                to_text(comment.deleted_at),
+            ],
+        )?;
+    }
+    Ok(comments.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn expense(id: i64, updated_at: &str, deleted_at: Option<&str>) -> Expense {
+        serde_json::from_value(json!({
+            "id": id,
+            "group_id": 1,
+            "description": "Groceries",
+            "cost": "30.00",
+            "currency_code": "USD",
+            "date": "2023-01-02T00:00:00Z",
+            "updated_at": updated_at,
+            "deleted_at": deleted_at,
+            "users": [
+                {"user_id": 10, "paid_share": "30.00", "owed_share": "15.00"},
+                {"user": {"id": 20, "first_name": "Ada"}, "paid_share": "0.00", "owed_share": "15.00"}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn store_expenses_tracks_updates_and_deletions() {
+        let mut mirror = Mirror::open_in_memory().unwrap();
+
+        let tx = mirror.conn.transaction().unwrap();
+        let counts = store_expenses(
+            &tx,
+            &[
+                expense(1, "2023-01-03T00:00:00Z", None),
+                expense(2, "2023-01-04T00:00:00Z", None),
+            ],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        assert_eq!(counts, (2, 0));
+
+        let tx = mirror.conn.transaction().unwrap();
+        let counts = store_expenses(
+            &tx,
+            &[expense(
+                2,
+                "2023-01-05T00:00:00Z",
+                Some("2023-01-05T00:00:00Z"),
+            )],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        assert_eq!(counts, (0, 1));
+
+        let live: i64 = mirror
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM expenses WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(live, 1);
+
+        let shares: i64 = mirror
+            .connection()
+            .query_row(
+                "SELECT COUNT(*) FROM expense_shares WHERE expense_id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(shares, 2);
+
+        assert_eq!(
+            mirror.last_synced_at().unwrap().unwrap().to_rfc3339(),
+            "2023-01-05T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn store_comments_replaces_removed_comments() {
+        let mut mirror = Mirror::open_in_memory().unwrap();
+        let comment = |id: i64| -> Comment {
+            serde_json::from_value(json!({
+                "id": id,
+                "content": "Thanks",
+                "comment_type": "User",
+                "relation_type": "ExpenseComment",
+                "relation_id": 1,
+                "created_at": "2023-01-03T00:00:00Z"
+            }))
+            .unwrap()
+        };
+        let count = |mirror: &Mirror| -> i64 {
+            mirror
+                .connection()
+                .query_row("SELECT COUNT(*) FROM comments", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let tx = mirror.conn.transaction().unwrap();
+        store_expenses(&tx, &[expense(1, "2023-01-03T00:00:00Z", None)]).unwrap();
+        store_comments(&tx, 1, &[comment(5), comment(6)]).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(count(&mirror), 2);
+
+        let tx = mirror.conn.transaction().unwrap();
+        store_comments(&tx, 1, &[comment(6)]).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(count(&mirror), 1);
+
+        let tx = mirror.conn.transaction().unwrap();
+        store_comments(&tx, 1, &[]).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(count(&mirror), 0);
+    }
+}
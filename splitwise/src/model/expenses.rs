@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use anyhow::bail;
 use serde::Deserialize;
 use serde::Serialize;
 use serde::Serializer;
@@ -215,6 +216,10 @@ pub struct CreateExpenseRequest {
     /// Whether to split the expense equally among users.
     pub split_equally: bool,
 
+    /// Whether the expense is a payment between users, shown as a settle up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment: Option<bool>,
+
     /// Users by share if not splitting the expense equally.
     #[serde(flatten)]
     #[serde(serialize_with = "serialize_option_vec_user_by_shares")]
@@ -259,8 +264,20 @@ pub struct UpdateExpenseRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub category_id: Option<i64>,
 
-    /// The group to put this expense in.
-    pub group_id: i64,
+    /// The group to move this expense to, or 0 to take it out of its group.
+    /// The expense stays in its group if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<i64>,
+
+    /// Whether to remind involved users in advance of the next occurrence of
+    /// a recurring expense.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_reminder: Option<bool>,
+
+    /// Number of days in advance to remind involved users about the next
+    /// occurrence. One of `EMAIL_REMINDER_DAYS`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_reminder_in_advance: Option<i64>,
 
     /// Users by share if not splitting the expense equally.
     #[serde(flatten)]
@@ -269,6 +286,73 @@ pub struct UpdateExpenseRequest {
     pub users: Option<Vec<UserShare>>,
 }
 
+/// Cadence at which an expense repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatInterval {
+    /// Does not repeat.
+    Never,
+    /// Repeats every week.
+    Weekly,
+    /// Repeats every two weeks.
+    Fortnightly,
+    /// Repeats every month.
+    Monthly,
+    /// Repeats every year.
+    Yearly,
+}
+
+impl RepeatInterval {
+    /// Value used by the Splitwise API, eg `monthly`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepeatInterval::Never => "never",
+            RepeatInterval::Weekly => "weekly",
+            RepeatInterval::Fortnightly => "fortnightly",
+            RepeatInterval::Monthly => "monthly",
+            RepeatInterval::Yearly => "yearly",
+        }
+    }
+}
+
+impl std::fmt::Display for RepeatInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for RepeatInterval {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "never" => Ok(RepeatInterval::Never),
+            "weekly" => Ok(RepeatInterval::Weekly),
+            "fortnightly" => Ok(RepeatInterval::Fortnightly),
+            "monthly" => Ok(RepeatInterval::Monthly),
+            "yearly" => Ok(RepeatInterval::Yearly),
+            _ => bail!(
+                "invalid repeat interval '{}': expected never, weekly, fortnightly, monthly or yearly",
+                s
+            ),
+        }
+    }
+}
+
+/// Values accepted by Splitwise for `email_reminder_in_advance`.
+pub const EMAIL_REMINDER_DAYS: [i64; 10] = [-1, 0, 1, 2, 3, 4, 5, 6, 7, 14];
+
+/// Checks that a number of days is accepted for `email_reminder_in_advance`.
+pub fn validate_email_reminder_days(days: i64) -> Result<i64, anyhow::Error> {
+    if EMAIL_REMINDER_DAYS.contains(&days) {
+        return Ok(days);
+    }
+    bail!(
+        "invalid reminder of {} days in advance: expected -1 to 7, or 14",
+        days
+    )
+}
+
 /// User with share information associated with the expense.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UserShare {
@@ -299,6 +383,15 @@ pub struct UserShare {
     pub net_balance: Option<String>,
 }
 
+impl UserShare {
+    /// ID of the user of the share, from `user_id` or else from `user`, which
+    /// is the only one set in responses from Splitwise.
+    pub fn user_id(&self) -> Option<i64> {
+        self.user_id
+            .or_else(|| self.user.as_ref().and_then(|u| u.id))
+    }
+}
+
 impl Default for CreateExpenseRequest {
     fn default() -> Self {
         Self {
@@ -311,11 +404,54 @@ impl Default for CreateExpenseRequest {
             category_id: 0,
             group_id: 0,
             split_equally: true,
+            payment: None,
             users: None,
         }
     }
 }
 
+impl From<&Expense> for CreateExpenseRequest {
+    /// Builds a request that would create a copy of the given expense. Values
+    /// missing from the expense fall back to those of
+    /// `CreateExpenseRequest::default()`.
+    fn from(expense: &Expense) -> Self {
+        let default = Self::default();
+        let users: Option<Vec<UserShare>> = expense.users.as_ref().map(|shares| {
+            shares
+                .iter()
+                .map(|share| UserShare {
+                    user_id: share.user_id(),
+                    paid_share: share.paid_share.clone(),
+                    owed_share: share.owed_share.clone(),
+                    ..UserShare::default()
+                })
+                .collect()
+        });
+        Self {
+            cost: expense.cost.clone().unwrap_or(default.cost),
+            description: expense.description.clone().unwrap_or(default.description),
+            details: expense.details.clone(),
+            date: expense.date.unwrap_or(default.date),
+            repeat_interval: expense
+                .repeat_interval
+                .clone()
+                .unwrap_or(default.repeat_interval),
+            currency_code: expense
+                .currency_code
+                .clone()
+                .unwrap_or(default.currency_code),
+            category_id: expense
+                .category_id
+                .or_else(|| expense.category.as_ref().and_then(|c| c.id))
+                .unwrap_or(default.category_id),
+            group_id: expense.group_id.unwrap_or(default.group_id),
+            split_equally: users.is_none(),
+            payment: expense.payment,
+            users,
+        }
+    }
+}
+
 fn serialize_option_vec_user_by_shares<S: Serializer>(
     vec: &Option<Vec<UserShare>>,
     serializer: S,
@@ -347,3 +483,25 @@ fn serialize_option_vec_user_by_shares<S: Serializer>(
 
     map.serialize(serializer)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn repeat_interval_and_reminder_days_are_validated() {
+        assert_eq!(
+            RepeatInterval::from_str("Monthly").unwrap(),
+            RepeatInterval::Monthly
+        );
+        assert_eq!(RepeatInterval::Fortnightly.to_string(), "fortnightly");
+        assert!(RepeatInterval::from_str("daily").is_err());
+
+        assert_eq!(validate_email_reminder_days(-1).unwrap(), -1);
+        assert_eq!(validate_email_reminder_days(14).unwrap(), 14);
+        assert!(validate_email_reminder_days(8).is_err());
+        assert!(validate_email_reminder_days(-2).is_err());
+    }
+}
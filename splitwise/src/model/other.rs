@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::model::expenses::CreateExpenseRequest;
 use crate::model::expenses::Expense;
 use crate::model::shared::Image;
 
@@ -77,3 +78,17 @@ pub struct ParseSentenceResponse {
     /// Error that occurred during this request.
     pub error: Option<String>,
 }
+
+/// Expense parsed from a natural language sentence that has not been saved
+/// yet. See `OtherSvc::preview_sentence`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentencePreview {
+    /// Request that will create the parsed expense.
+    pub request: CreateExpenseRequest,
+
+    /// Expense object parsed from the sentence input.
+    pub expense: Expense,
+
+    /// Confidence value for the parsed expense.
+    pub confidence: f64,
+}
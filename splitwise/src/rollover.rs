@@ -0,0 +1,260 @@
+//! Rolling a group over into a new one, eg at the end of a year.
+//!
+//! The new group gets the members and settings of the old one, and what
+//! members owe each other in the old group, as listed in its simplified debts,
+//! is carried over as one opening balance expense per pair of members. The old
+//! group can then be settled, so that its balances are all zero.
+
+use std::str::FromStr;
+
+use anyhow::bail;
+use chrono::DateTime;
+use chrono::Utc;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::client::Client;
+use crate::model::expenses::CreateExpenseRequest;
+use crate::model::expenses::Expense;
+use crate::model::expenses::UserShare;
+use crate::model::groups::Group;
+use crate::model::groups::GroupCreateRequest;
+use crate::model::groups::GroupUser;
+use crate::model::Debt;
+
+/// Options of `rollover`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolloverOptions {
+    /// Name of the new group.
+    pub name: String,
+
+    /// Date of the opening balance and settlement expenses.
+    pub date: DateTime<Utc>,
+
+    /// Also settle the old group, with one expense per debt cancelling it.
+    pub settle_old_group: bool,
+}
+
+/// Result of `rollover`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolloverReport {
+    /// Group created for the new period.
+    pub group: Group,
+
+    /// Opening balance expenses created in the new group.
+    pub opening_balances: Vec<Expense>,
+
+    /// Payments settling the old group, if requested.
+    pub settlements: Vec<Expense>,
+
+    /// Opening balances or settlements that could not be created. The
+    /// rollover is incomplete if there are any.
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// Creates a new group with the members and settings of an existing group,
+/// carrying over its outstanding balances, and optionally settles the old
+/// group.
+///
+/// Once the new group exists, failures to create expenses are listed in the
+/// report's `errors` rather than returned as an error, so that the caller
+/// knows what was created and can finish the rollover by hand instead of
+/// running it again. The old group is left untouched if any balance fails to
+/// carry over.
+pub async fn rollover(
+    client: &Client,
+    group_id: i64,
+    options: &RolloverOptions,
+) -> Result<RolloverReport, anyhow::Error> {
+    let old = client.groups().get_group(group_id).await?;
+    let current_user = client.users().get_current_user().await?;
+    let old_name = old.name.clone().unwrap_or_else(|| group_id.to_string());
+
+    // The current user is added to new groups by Splitwise
+    let members = old
+        .members
+        .iter()
+        .flatten()
+        .filter(|m| m.id.is_some() && m.id != current_user.id)
+        .map(|m| GroupUser {
+            user_id: m.id,
+            ..GroupUser::default()
+        })
+        .collect::<Vec<_>>();
+    let group = client
+        .groups()
+        .create_group(GroupCreateRequest {
+            name: options.name.clone(),
+            group_type: old.group_type.clone(),
+            simplify_by_default: old.simplify_by_default,
+            users: Some(members),
+        })
+        .await?;
+    let new_id = match group.id {
+        Some(id) => id,
+        None => bail!("no group was created"),
+    };
+
+    let debts = old.simplified_debts.clone().unwrap_or_default();
+    let requests = debts
+        .iter()
+        .filter_map(|debt| {
+            debt_request(
+                debt,
+                new_id,
+                options.date,
+                format!("Opening balance from {}", old_name),
+                false,
+            )
+        })
+        .collect();
+    let (opening_balances, mut errors) = create_all(client, requests).await;
+
+    let settlements = if options.settle_old_group && errors.is_empty() {
+        let requests = debts
+            .iter()
+            .filter_map(|debt| {
+                debt_request(
+                    debt,
+                    group_id,
+                    options.date,
+                    format!("Balance carried over to {}", options.name),
+                    true,
+                )
+            })
+            .collect();
+        let (settlements, settle_errors) = create_all(client, requests).await;
+        errors.extend(settle_errors);
+        settlements
+    } else {
+        Vec::new()
+    };
+
+    Ok(RolloverReport {
+        group,
+        opening_balances,
+        settlements,
+        errors,
+    })
+}
+
+/// Creates the expenses, returning the created ones and an error for each
+/// one that was not.
+async fn create_all(
+    client: &Client,
+    requests: Vec<CreateExpenseRequest>,
+) -> (Vec<Expense>, Vec<String>) {
+    let mut created = Vec::new();
+    let mut errors = Vec::new();
+    let results = client.expenses().create_expenses(requests.clone()).await;
+    for (request, result) in requests.iter().zip(results) {
+        match result {
+            Ok(expenses) => created.extend(expenses),
+            Err(e) => errors.push(format!("{}: {}", request.description, e)),
+        }
+    }
+    (created, errors)
+}
+
+/// Expense in the group recreating a debt, where the creditor paid what the
+/// debtor owes, or payment settling it if `settle` is set, where the debtor
+/// pays the creditor back. Returns `None` for incomplete or zero debts.
+fn debt_request(
+    debt: &Debt,
+    group_id: i64,
+    date: DateTime<Utc>,
+    description: String,
+    settle: bool,
+) -> Option<CreateExpenseRequest> {
+    let (from, to, amount) = (debt.from?, debt.to?, debt.amount.as_deref()?);
+    if Decimal::from_str(amount.trim()).ok()? <= Decimal::ZERO {
+        return None;
+    }
+    let (payer, ower) = if settle { (from, to) } else { (to, from) };
+    let share = |user_id, paid: &str, owed: &str| UserShare {
+        user_id: Some(user_id),
+        paid_share: Some(paid.to_string()),
+        owed_share: Some(owed.to_string()),
+        ..UserShare::default()
+    };
+    Some(CreateExpenseRequest {
+        cost: amount.to_string(),
+        description,
+        date,
+        currency_code: debt
+            .currency_code
+            .clone()
+            .unwrap_or_else(|| CreateExpenseRequest::default().currency_code),
+        group_id,
+        split_equally: false,
+        payment: Some(settle),
+        users: Some(vec![
+            share(payer, amount, "0.00"),
+            share(ower, "0.00", amount),
+        ]),
+        ..CreateExpenseRequest::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn debt_request_carries_over_or_settles_debt() {
+        let debt = Debt {
+            from: Some(1),
+            to: Some(2),
+            amount: Some("12.50".to_string()),
+            currency_code: Some("EUR".to_string()),
+        };
+        let date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let shares = |request: &CreateExpenseRequest| {
+            request
+                .users
+                .iter()
+                .flatten()
+                .map(|s| {
+                    (
+                        s.user_id.unwrap(),
+                        s.paid_share.clone().unwrap(),
+                        s.owed_share.clone().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let opening = debt_request(&debt, 9, date, "Opening".to_string(), false).unwrap();
+        assert_eq!(opening.group_id, 9);
+        assert_eq!(opening.payment, Some(false));
+        assert_eq!(opening.cost, "12.50");
+        assert_eq!(opening.currency_code, "EUR");
+        assert_eq!(
+            shares(&opening),
+            vec![
+                (2, "12.50".to_string(), "0.00".to_string()),
+                (1, "0.00".to_string(), "12.50".to_string())
+            ]
+        );
+
+        let settlement = debt_request(&debt, 8, date, "Settle".to_string(), true).unwrap();
+        assert_eq!(settlement.payment, Some(true));
+        assert_eq!(
+            shares(&settlement),
+            vec![
+                (1, "12.50".to_string(), "0.00".to_string()),
+                (2, "0.00".to_string(), "12.50".to_string())
+            ]
+        );
+
+        let settled = Debt {
+            amount: Some("0.00".to_string()),
+            ..debt
+        };
+        assert!(debt_request(&settled, 9, date, "Opening".to_string(), false).is_none());
+    }
+}